@@ -0,0 +1,241 @@
+use {seeded_rng, Board, Card, Color, OwnedCard};
+use bitboard::BitBoard;
+use preview::Outcome;
+use state::{GameState, Move};
+use transposition::{Bound, TranspositionTable};
+use zobrist::{ZobristHash, ZobristKeys};
+
+/// The seed for the keys used to hash positions while searching.
+const KEY_SEED: u64 = 0x7E7A_3A57;
+/// How many positions the searcher remembers by default.
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 16;
+
+/// How good a position is for the given color, without looking ahead: how many more cards they own
+/// than their opponent, counting the cards in their hands.
+pub fn evaluate(state: &GameState, color: Color) -> f64 {
+  let score = state.score(true);
+  score.get(color) as f64 - score.get(color.opponent()) as f64
+}
+
+/// Works out the board after a placement that starts no battles on a `BitBoard`, which is quicker
+/// than working out every outcome. Returns `None` if the board isn't 4x4 or the card battles.
+fn quiet_placement(board: &Board, row: usize, column: usize, card: OwnedCard) -> Option<Board> {
+  let mut bits = match BitBoard::from_board(board) {
+    Some(b) => b,
+    None => return None
+  };
+  let cell = BitBoard::cell(row, column);
+  bits.place(cell, card.card, card.color);
+  if !bits.relations(cell).0.is_empty() {
+    return None;
+  }
+  // Without battles nothing is rolled.
+  bits.run_battles(cell, &mut seeded_rng(0));
+  Some(bits.to_board())
+}
+
+/// What came of a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+  /// The move with the best expected value, or `None` if there are no moves.
+  pub best: Option<Move>,
+  /// The expected value of the position for the player to move, as `evaluate` counts it.
+  pub value: f64,
+  /// How many placements were looked ahead. At least one, so that there is always a best move.
+  pub depth: u8,
+  /// How many positions were looked at.
+  pub nodes: u64
+}
+
+/// Finds good moves by looking ahead, weighing every way the battles of each move could go by its
+/// chance.
+///
+/// The searcher remembers positions it has seen in a transposition table, so searching the same
+/// match again, or deeper, is quicker. The card battled first is always the one battled first by
+/// default.
+pub struct Searcher {
+  /// The keys for the size of board last searched, and that size.
+  keys: Option<((usize, usize), ZobristKeys)>,
+  /// The value of each position seen and the best card to play and where. The card is kept rather
+  /// than its place in the hand, since positions whose hands are in another order hash the same.
+  table: TranspositionTable<(f64, Option<(Card, usize, usize)>)>,
+  nodes: u64
+}
+
+impl Default for Searcher {
+  fn default() -> Self {
+    Searcher::with_table_size(DEFAULT_TABLE_SIZE)
+  }
+}
+
+impl Searcher {
+  pub fn new() -> Self {
+    Searcher::default()
+  }
+
+  pub fn with_table_size(slots: usize) -> Self {
+    Searcher {
+      keys: None,
+      table: TranspositionTable::new(slots),
+      nodes: 0
+    }
+  }
+
+  /// Forgets every position seen.
+  pub fn clear(&mut self) {
+    self.table.clear();
+  }
+
+  /// Searches the given number of placements ahead.
+  ///
+  /// Panics if the depth is 0: every search looks at least one placement ahead.
+  pub fn search(&mut self, state: &GameState, depth: u8) -> SearchResult {
+    assert!(depth > 0, "A search must look at least one placement ahead");
+    let size = (state.board.width(), state.board.height());
+    if self.keys.as_ref().map(|&(s, _)| s != size).unwrap_or(true) {
+      self.keys = Some((size, ZobristKeys::new(KEY_SEED, size.0, size.1)));
+      self.table.clear();
+    }
+    self.nodes = 0;
+    let hash = self.keys().hash(state);
+    let (value, best) = self.value(state, hash, depth);
+    let hand = state.hand(state.to_move);
+    SearchResult {
+      best: best.and_then(|(card, row, column)| hand.iter().position(|c| *c == card).map(|i| Move::new(i, row, column))),
+      value: value,
+      depth: depth,
+      nodes: self.nodes
+    }
+  }
+
+  /// Searches one placement deeper at a time, up to the given depth, passing each result on as it
+  /// is found. Panics if the depth is 0, like `search`.
+  pub fn search_iteratively<F: FnMut(&SearchResult)>(&mut self, state: &GameState, depth: u8, mut each: F) -> SearchResult {
+    assert!(depth > 0, "A search must look at least one placement ahead");
+    let mut result = self.search(state, 1);
+    each(&result);
+    for d in 2..=depth {
+      result = self.search(state, d);
+      each(&result);
+    }
+    result
+  }
+
+  fn keys(&self) -> &ZobristKeys {
+    &self.keys.as_ref().expect("Keys are made before searching").1
+  }
+
+  /// The expected value of a position for the player to move, and the best card to play and where.
+  fn value(&mut self, state: &GameState, hash: ZobristHash, depth: u8) -> (f64, Option<(Card, usize, usize)>) {
+    self.nodes += 1;
+    let color = state.to_move;
+    if depth == 0 || state.is_over() {
+      return (evaluate(state, color), None);
+    }
+    if let Some(entry) = self.table.get(hash.value()) {
+      if entry.depth >= depth && entry.bound == Bound::Exact {
+        return entry.value;
+      }
+    }
+    let mut best: Option<(f64, (Card, usize, usize))> = None;
+    let hand = state.hand(color).to_vec();
+    for (i, card) in hand.iter().enumerate() {
+      // The same card from another slot of the hand plays the same way.
+      if hand[..i].contains(card) {
+        continue;
+      }
+      for (row, column) in state.empty_spaces() {
+        let placed = OwnedCard::new(*card, color);
+        let outcomes = match quiet_placement(&state.board, row, column, placed.clone()) {
+          Some(board) => vec![Outcome {
+            board: board,
+            chance: 1.0
+          }],
+          None => state.board.outcomes(row, column, placed).expect("Space is empty")
+        };
+        let mut expected = 0.0;
+        for outcome in outcomes {
+          let mut next = state.clone();
+          next.hand_mut(color).remove(i);
+          next.board = outcome.board;
+          next.to_move = color.opponent();
+          let next_hash = self.keys().update(hash, state, row, column, &next);
+          let (value, _) = self.value(&next, next_hash, depth - 1);
+          // Whatever is good for the opponent is bad for this player.
+          expected -= outcome.chance * value;
+        }
+        if best.map(|(value, _)| expected > value).unwrap_or(true) {
+          best = Some((expected, (*card, row, column)));
+        }
+      }
+    }
+    let result = match best {
+      Some((value, mv)) => (value, Some(mv)),
+      None => (evaluate(state, color), None)
+    };
+    self.table.insert(hash.value(), depth, Bound::Exact, result);
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixtures::new_match;
+  use generator::BoardGenerator;
+  use rand::Rng;
+
+  #[test]
+  fn quiet_placements_match_outcomes() {
+    for seed in 0..100 {
+      let mut rng = seeded_rng(seed);
+      let mut board = BoardGenerator::new().seed(seed).generate();
+      for _ in 0..8 {
+        let empty: Vec<(usize, usize)> = (1..5).flat_map(|r| (1..5).map(move |c| (r, c))).filter(|&(r, c)| board.space(r, c).is_empty()).collect();
+        if empty.is_empty() {
+          break;
+        }
+        let (row, column) = empty[rng.gen_range(0, empty.len())];
+        let card = OwnedCard::new(Card::random(&mut rng), if rng.gen() { Color::Red } else { Color::Blue });
+        let outcomes = board.outcomes(row, column, card.clone()).unwrap();
+        if let Some(quiet) = quiet_placement(&board, row, column, card.clone()) {
+          assert_eq!(outcomes.len(), 1);
+          assert_eq!(outcomes[0].board, quiet);
+        }
+        board.add_card(row, column, card);
+        board.run_battles_pos_with_rng(row, column, &mut rng);
+      }
+    }
+  }
+
+  #[test]
+  fn depth_one_picks_a_move() {
+    for seed in 0..20 {
+      let state = new_match(seed);
+      let result = Searcher::new().search(&state, 1);
+      assert_eq!(result.depth, 1);
+      assert!(state.is_legal(result.best.expect("There are legal moves")));
+    }
+  }
+
+  #[test]
+  #[should_panic]
+  fn depth_zero_is_refused() {
+    Searcher::new().search(&new_match(1), 0);
+  }
+
+  #[test]
+  fn remembered_moves_follow_the_card() {
+    for seed in 0..20 {
+      let state = new_match(seed);
+      let mut searcher = Searcher::new();
+      let best = searcher.search(&state, 2).best.unwrap();
+      // The same position with the hand the other way around hashes the same.
+      let mut reversed = state.clone();
+      reversed.hand_mut(Color::Blue).reverse();
+      let again = searcher.search(&reversed, 2).best.unwrap();
+      assert_eq!(reversed.hand(Color::Blue)[again.card], state.hand(Color::Blue)[best.card]);
+      assert_eq!((again.row, again.column), (best.row, best.column));
+    }
+  }
+}
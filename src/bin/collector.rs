@@ -0,0 +1,53 @@
+extern crate tetra_master;
+
+use tetra_master::collection::{self, Collection, MAX_CARDS};
+use tetra_master::collector::{self, CollectorPoints};
+
+use std::env::args;
+use std::path::PathBuf;
+
+fn list(ids: &[u8]) -> String {
+  ids.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+fn main() {
+  let path = args().nth(1).map(PathBuf::from).unwrap_or_else(collection::default_path);
+  let collection = match Collection::load(&path) {
+    Ok(c) => c,
+    Err(e) => {
+      println!("Could not load collection from {}: {}", path.display(), e);
+      println!("Usage: collector (collection file)");
+      return;
+    }
+  };
+  let points = CollectorPoints::calculate(&collection.cards);
+  let rank = points.rank();
+  println!("{} cards ({} max)", collection.len(), MAX_CARDS);
+  println!("Collector points: {} ({})", points.total(), rank.name());
+  println!("  Card types: {} ({} points)", points.types, points.type_points());
+  println!("  Arrow patterns: {} ({} points)", points.arrow_patterns, points.arrow_points());
+  println!("  Flexible types: {}, Assault types: {} ({} points)",
+    points.flexible_types,
+    points.assault_types,
+    points.class_points());
+  let next = match rank.next() {
+    Some(r) => r,
+    None => {
+      println!("There are no ranks above {}.", rank.name());
+      return;
+    }
+  };
+  println!("Next rank: {} at {} points ({} more)",
+    next.name(),
+    next.min_points(),
+    next.min_points() - points.total());
+  let missing = collector::missing_types(&collection.cards);
+  if !missing.is_empty() {
+    println!("Missing card types ({} points each): {}", collector::TYPE_POINTS, list(&missing));
+  }
+  println!("New arrow patterns are worth {} points each.", collector::ARROW_POINTS);
+  let missing_assault = collector::missing_assault(&collection.cards);
+  if !missing_assault.is_empty() {
+    println!("Owned types without an Assault card: {}", list(&missing_assault));
+  }
+}
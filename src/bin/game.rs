@@ -14,10 +14,22 @@ use rand::{thread_rng, Rng};
 
 use rodio::{Sink, Source};
 
-use tetra_master::{Board, Space, OwnedCard, PlacedCard, Color as CardColor};
+use tetra_master::{BattleResult, Board, Card, Space, OwnedCard, Color as CardColor};
+use tetra_master::bot::{Bot, BotError};
+use tetra_master::collection::{Collection, CollectedCard, Stakes, Spoils};
+use tetra_master::fair::{RollChecker, SeedChain};
+use tetra_master::net::{self, Connection, HostedMatch, Message, Played, Rules, Spectators, PROTOCOL_VERSION, RECONNECT_GRACE};
+use tetra_master::report::{BattleReport, PlacementReport};
+use tetra_master::state::{GameState, Move};
 
+use std::cmp::min;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
 use std::io::BufReader;
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
 
 struct ArrowImages {
   north: conrod::image::Id,
@@ -33,6 +45,569 @@ struct ArrowImages {
 const WIDTH: u32 = 504;
 const HEIGHT: u32 = 744;
 
+/// How many cards each player brings to a match on a standard board.
+const HAND_SIZE: usize = 5;
+/// The board sizes that can be played on, as width and height.
+const BOARD_SIZES: &'static [(usize, usize)] = &[(3, 3), (4, 4), (5, 5), (5, 4)];
+/// How many cards a new player starts their collection with.
+const STARTER_SIZE: usize = 10;
+/// How many cards of the collection are shown at once when choosing a hand.
+const DECK_PAGE_SIZE: usize = 15;
+/// How many seconds to wait between tries to reconnect to the host.
+const RECONNECT_WAIT: u64 = 2;
+/// How many milliseconds a placed card takes to slide onto its space.
+const PLACE_MILLIS: u64 = 250;
+/// How many milliseconds each battle's numbers are shown before the cards it flips turn over.
+const BATTLE_MILLIS: u64 = 900;
+/// How many milliseconds a card takes to turn over to its new owner.
+const FLIP_MILLIS: u64 = 300;
+/// How many milliseconds apart the cards of a combo start turning over.
+const COMBO_MILLIS: u64 = 120;
+/// How far a placed card slides from, across and down.
+const SLIDE_DISTANCE: f64 = 160.0;
+/// How far battling cards lunge at each other.
+const CLASH_DISTANCE: f64 = 12.0;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Screen {
+  DeckSelect,
+  Match
+}
+
+/// Where a card on the board came from, so it can change hands when the match is over.
+enum CardSource {
+  /// An index into the player's collection.
+  Collection(usize),
+  /// A card the opponent brought to the match.
+  Opponent(CollectedCard)
+}
+
+/// Who the player is playing against.
+enum Opponent {
+  /// The computer, which plays at random.
+  Computer,
+  /// Waiting for a player to connect to this game.
+  Waiting,
+  /// A player on another machine.
+  Remote(Remote),
+  /// Another program, which plays red.
+  Bot(BotPlayer),
+  /// Another player at this computer, who plays red. The players take turns with the mouse.
+  Local
+}
+
+struct Remote {
+  connection: Connection,
+  /// Whether this game is the host, which keeps the match and rolls every battle.
+  is_host: bool,
+  /// The host's copy of the match, while one is being played.
+  hosted: Option<HostedMatch>,
+  /// The joining player's check on the host's rolls, while a match is being played.
+  checker: Option<RollChecker>,
+  /// The cards the joining player brings, once the host has them.
+  hand: Option<Vec<Card>>,
+  /// Whether the joining player has sent their cards and is waiting for the match to start.
+  joined: bool,
+  /// The token the joining player can come back to the match with.
+  token: Option<String>,
+  /// Where the host is, for the joining player to reconnect to.
+  address: Option<String>,
+  /// Set while the connection is lost during a match.
+  lost: Option<Lost>,
+  /// What the host said about the placements since the match was last shown, for animating the
+  /// last one.
+  reported: Vec<Message>
+}
+
+/// A match waiting for the connection to the other player to come back.
+struct Lost {
+  since: Instant,
+  /// The joining player's try to connect to the host again, if one is being made.
+  attempt: Option<Receiver<std::io::Result<Connection>>>,
+  /// When the joining player may next try to connect.
+  retry: Instant
+}
+
+impl Lost {
+  fn new() -> Self {
+    Lost {
+      since: Instant::now(),
+      attempt: None,
+      retry: Instant::now()
+    }
+  }
+}
+
+impl Remote {
+  fn new(connection: Connection, is_host: bool) -> Self {
+    Remote {
+      connection: connection,
+      is_host: is_host,
+      hosted: None,
+      checker: None,
+      hand: None,
+      joined: false,
+      token: None,
+      address: None,
+      lost: None,
+      reported: Vec::new()
+    }
+  }
+
+  /// Whether the connection can be lost without leaving the match.
+  fn can_resume(&self) -> bool {
+    let playing = match (self.hosted.as_ref(), self.checker.as_ref()) {
+      (Some(hosted), _) => !hosted.state.is_over(),
+      (None, Some(checker)) => !checker.state().is_over() && self.address.is_some(),
+      _ => false
+    };
+    playing && self.token.is_some() && self.connection.lost()
+  }
+
+  /// Gets the next message from the other player. Nothing is received while the connection is lost.
+  fn try_recv(&self) -> Option<Message> {
+    if self.lost.is_some() {
+      return None;
+    }
+    self.connection.try_recv()
+  }
+
+  fn send(&mut self, message: &Message) {
+    if self.lost.is_some() {
+      return;
+    }
+    if let Err(e) = self.connection.send(message) {
+      println!("Could not send to the other player: {}", e);
+    }
+  }
+
+  /// Passes on what came of a move played on the host's copy of the match, to the other player and
+  /// anyone watching.
+  ///
+  /// Returns the cards to pick from when this game's player must pick which card to battle first.
+  fn share(&mut self, is_local: bool, played: Result<Played, String>, spectators: &mut Spectators) -> Vec<(usize, usize)> {
+    match played {
+      Ok(Played::Done(messages)) => {
+        for message in &messages {
+          self.send(message);
+          spectators.send(message);
+        }
+        self.reported.extend(messages);
+        Vec::new()
+      },
+      Ok(Played::Waiting(messages)) => {
+        for message in &messages {
+          self.send(message);
+        }
+        Vec::new()
+      },
+      Ok(Played::Choose(targets)) => if is_local {
+        targets
+      } else {
+        self.send(&Message::Choose(targets));
+        Vec::new()
+      },
+      Err(reason) => {
+        if is_local {
+          println!("Illegal move: {}", reason);
+        } else {
+          self.send(&Message::Illegal(reason));
+        }
+        Vec::new()
+      }
+    }
+  }
+}
+
+/// Reads how to find an opponent from the command line: `--host [port]` to wait for a player to
+/// connect, or `--join <address>` to connect to a host.
+///
+/// A host keeps listening after its player connects, so others can connect to watch.
+fn opponent_from_args() -> (Opponent, Option<Receiver<std::io::Result<Connection>>>) {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  match args.get(0).map(|x| x.as_str()) {
+    Some("--host") => {
+      let port = args.get(1).and_then(|p| p.parse().ok()).unwrap_or(net::DEFAULT_PORT);
+      match net::accept_all(("0.0.0.0", port)) {
+        Ok(rx) => {
+          println!("Waiting for a player on port {}", port);
+          (Opponent::Waiting, Some(rx))
+        },
+        Err(e) => panic!("Could not listen on port {}: {}", port, e)
+      }
+    },
+    Some("--join") => {
+      let addr = match args.get(1) {
+        Some(a) if a.contains(':') => a.clone(),
+        Some(a) => format!("{}:{}", a, net::DEFAULT_PORT),
+        None => panic!("Usage: game --join <address>")
+      };
+      match Connection::connect(&addr[..]) {
+        Ok(c) => {
+          let mut remote = Remote::new(c, false);
+          remote.address = Some(addr);
+          (Opponent::Remote(remote), None)
+        },
+        Err(e) => panic!("Could not connect to {}: {}", addr, e)
+      }
+    },
+    Some("--bot") => {
+      let command = args[1..].join(" ");
+      match Bot::launch(&command) {
+        Ok(b) => (Opponent::Bot(BotPlayer::new(b)), None),
+        Err(e) => panic!("Could not start the bot {}: {}", command, e)
+      }
+    },
+    Some("--hot-seat") => (Opponent::Local, None),
+    _ => (Opponent::Computer, None)
+  }
+}
+
+/// Gets a match against the computer or a bot as a state, for a bot to look at.
+fn local_state(board: &Board, player_hand: &[OwnedCard], opponent_hand: &[OwnedCard], to_move: CardColor) -> GameState {
+  let cards = |hand: &[OwnedCard]| hand.iter().map(|c| c.card).collect();
+  GameState::new(board.clone(), cards(player_hand), cards(opponent_hand), to_move)
+}
+
+/// Asks a bot for its move, and which card to battle first if it can battle more than one.
+fn bot_move(bot: &mut Bot, state: &GameState) -> Result<(Move, Option<(usize, usize)>), BotError> {
+  let mv = bot.choose_move(state)?;
+  let targets = state.battle_targets(mv);
+  if targets.len() > 1 {
+    bot.choose_first(&targets).map(|first| (mv, Some(first)))
+  } else {
+    Ok((mv, None))
+  }
+}
+
+type BotReply = Result<(Move, Option<(usize, usize)>), BotError>;
+
+/// A bot playing red. The bot is handed to another thread while it thinks, so the window keeps
+/// drawing, and its reply is polled like a connection's.
+struct BotPlayer {
+  /// The bot, unless it is thinking.
+  bot: Option<Bot>,
+  /// The bot and its move, once it has thought.
+  thinking: Option<Receiver<(Bot, BotReply)>>,
+  /// A match started while the bot was thinking, to tell it about once it's back.
+  restart: Option<GameState>
+}
+
+impl BotPlayer {
+  fn new(bot: Bot) -> Self {
+    BotPlayer {
+      bot: Some(bot),
+      thinking: None,
+      restart: None
+    }
+  }
+
+  /// Starts the bot thinking about its move, unless it already is.
+  fn think(&mut self, state: GameState) {
+    let mut bot = match self.bot.take() {
+      Some(b) => b,
+      None => return
+    };
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+      let reply = bot_move(&mut bot, &state);
+      let _ = tx.send((bot, reply));
+    });
+    self.thinking = Some(rx);
+  }
+
+  /// Gets the bot's move if it has one, without waiting. A move thought of for a match that was
+  /// started over is thrown away.
+  fn poll(&mut self) -> Option<BotReply> {
+    let (bot, reply) = match self.thinking.as_ref().map(Receiver::try_recv) {
+      Some(Ok(thought)) => thought,
+      Some(Err(TryRecvError::Empty)) | None => return None,
+      Some(Err(TryRecvError::Disconnected)) => {
+        self.thinking = None;
+        return Some(Err(BotError::Closed));
+      }
+    };
+    self.thinking = None;
+    self.bot = Some(bot);
+    match self.restart.take() {
+      Some(state) => self.start(&state).err().map(Err),
+      None => Some(reply)
+    }
+  }
+
+  /// Tells the bot a match is starting, once it's done thinking if it is.
+  fn start(&mut self, state: &GameState) -> Result<(), BotError> {
+    match self.bot {
+      Some(ref mut bot) => bot.start(CardColor::Red, state),
+      None => {
+        self.restart = Some(state.clone());
+        Ok(())
+      }
+    }
+  }
+
+  /// Tells the bot what happened after a placement. A bot that is thinking isn't told, since it's
+  /// sent the whole match with its next turn anyway.
+  fn tell(&mut self, color: CardColor, mv: Move, card: Card, report: &PlacementReport) -> Result<(), BotError> {
+    match self.bot {
+      Some(ref mut bot) => bot.tell(color, mv, card, report),
+      None => Ok(())
+    }
+  }
+}
+
+/// Gets a hand of a networked match as cards to show.
+fn owned_hand(state: &GameState, color: CardColor) -> Vec<OwnedCard> {
+  state.hand(color).iter().map(|c| OwnedCard::new(*c, color)).collect()
+}
+
+/// Shows a networked match on screen for the player of the given color, returning whether it is
+/// their turn.
+fn show_state(state: &GameState, color: CardColor, board: &mut Board, player_hand: &mut Vec<OwnedCard>, opponent_hand: &mut Vec<OwnedCard>) -> bool {
+  *board = state.board.clone();
+  *player_hand = owned_hand(state, color);
+  *opponent_hand = owned_hand(state, color.opponent());
+  state.to_move == color
+}
+
+/// Something that happens on the board over time.
+enum Animation {
+  /// A card slides onto its space.
+  Place((usize, usize)),
+  /// Two cards battle, with their numbers shown over them.
+  Clash(BattleReport),
+  /// A card turns over to a new owner.
+  Flip((usize, usize), CardColor)
+}
+
+struct Step {
+  /// When the step starts, in milliseconds after the card was placed.
+  start: u64,
+  length: u64,
+  animation: Animation
+}
+
+/// How a space on the board is drawn at the moment.
+struct Look {
+  /// How far the card is drawn from its space.
+  offset: (f64, f64),
+  /// How wide the card is drawn, as a share of its full width.
+  width: f64,
+  /// Whether the card is winning the battle being shown.
+  winner: bool,
+  /// The card's number in the battle being shown.
+  number: Option<u8>
+}
+
+impl Look {
+  fn still() -> Self {
+    Look {
+      offset: (0.0, 0.0),
+      width: 1.0,
+      winner: false,
+      number: None
+    }
+  }
+}
+
+/// A placement shown over time, starting from the board as it was before the card was placed.
+struct Animations {
+  board: Board,
+  steps: Vec<Step>,
+  /// Whether cards slide, lunge and turn over. If not, only the numbers of each battle are shown,
+  /// and cards change color as soon as they are taken.
+  moving: bool,
+  started: Instant
+}
+
+impl Animations {
+  /// Lays out the animations for a placement, given the board before the card was placed. Returns
+  /// `None` if there is nothing to show, which is when the cards don't move and there are no
+  /// battles.
+  fn new(before: &Board, card: OwnedCard, report: &PlacementReport, moving: bool) -> Option<Animations> {
+    if !moving && report.battles.is_empty() {
+      return None;
+    }
+    let color = card.color;
+    let mut board = before.clone();
+    board.add_card(report.row, report.column, card);
+    // The board as the steps so far leave it, for finding the color a flipped card turns.
+    let mut after = board.clone();
+    let mut steps = Vec::new();
+    let mut time = 0;
+    if moving {
+      steps.push(Step {
+        start: 0,
+        length: PLACE_MILLIS,
+        animation: Animation::Place((report.row, report.column))
+      });
+      time = PLACE_MILLIS;
+    }
+    for battle in &report.battles {
+      steps.push(Step {
+        start: time,
+        length: BATTLE_MILLIS,
+        animation: Animation::Clash(battle.clone())
+      });
+      time += BATTLE_MILLIS;
+      let winner = match battle.result {
+        BattleResult::Attacker => Some(battle.attacker),
+        BattleResult::Defender => Some(battle.defender),
+        BattleResult::Draw => None
+      };
+      let color = winner.and_then(|(row, col)| after.card(row, col)).map(|c| c.color);
+      if let Some(color) = color {
+        time = add_flips(&mut steps, &mut after, &battle.flipped, color, time, moving);
+      }
+    }
+    add_flips(&mut steps, &mut after, &report.taken, color, time, moving);
+    Some(Animations {
+      board: board,
+      steps: steps,
+      moving: moving,
+      started: Instant::now()
+    })
+  }
+
+  /// Lays out the animations for the last placement the host reported, given the board before the
+  /// card was placed. The messages are used up.
+  fn from_messages(board: &Board, messages: &mut Vec<Message>, moving: bool) -> Option<Animations> {
+    let placed = messages.iter().rposition(|m| match *m {
+      Message::Placed(..) => true,
+      _ => false
+    });
+    let shown = placed.and_then(|i| match messages[i] {
+      Message::Placed(color, mv, card) => {
+        let mut report = PlacementReport {
+          row: mv.row,
+          column: mv.column,
+          battles: Vec::new(),
+          taken: Vec::new()
+        };
+        for message in &messages[i + 1..] {
+          match *message {
+            Message::Battle(ref b) => report.battles.push(b.clone()),
+            Message::Taken(ref taken) => report.taken.extend(taken.iter().cloned()),
+            _ => {}
+          }
+        }
+        Animations::new(board, OwnedCard::new(card, color), &report, moving)
+      },
+      _ => None
+    });
+    messages.clear();
+    shown
+  }
+
+  /// How many milliseconds the animations have been playing.
+  fn elapsed(&self) -> u64 {
+    let elapsed = self.started.elapsed();
+    elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1_000_000
+  }
+
+  fn is_done(&self) -> bool {
+    let end = self.steps.iter().map(|s| s.start + s.length).max().unwrap_or(0);
+    self.elapsed() >= end
+  }
+
+  /// The board as it is shown now. Cards change color halfway through turning over.
+  fn board(&self) -> Board {
+    let now = self.elapsed();
+    let mut board = self.board.clone();
+    for step in &self.steps {
+      if let Animation::Flip((row, col), color) = step.animation {
+        if now >= step.start + step.length / 2 {
+          if let Space::Card(ref mut c) = *board.space_mut(row, col) {
+            c.card.color = color;
+          }
+        }
+      }
+    }
+    board
+  }
+
+  /// How the space at the given location is drawn now.
+  fn look(&self, row: usize, col: usize) -> Look {
+    let now = self.elapsed();
+    let mut look = Look::still();
+    for step in &self.steps {
+      if now < step.start || now >= step.start + step.length {
+        continue;
+      }
+      let progress = (now - step.start) as f64 / step.length as f64;
+      match step.animation {
+        Animation::Place(pos) if pos == (row, col) => {
+          // The card comes in from the hands, slowing down as it lands.
+          let left = (1.0 - progress) * (1.0 - progress);
+          look.offset = (SLIDE_DISTANCE * left, SLIDE_DISTANCE * left);
+        },
+        Animation::Clash(ref battle) => {
+          let (other, number, won) = if battle.attacker == (row, col) {
+            (battle.defender, battle.attack, battle.result == BattleResult::Attacker)
+          } else if battle.defender == (row, col) {
+            (battle.attacker, battle.defense, battle.result == BattleResult::Defender)
+          } else {
+            continue;
+          };
+          // Both cards lunge at each other, then the winner is shown.
+          let lunge = if self.moving && progress < 0.3 { (progress / 0.3 * PI).sin() * CLASH_DISTANCE } else { 0.0 };
+          look.offset = ((other.1 as f64 - col as f64) * lunge, (row as f64 - other.0 as f64) * lunge);
+          look.number = Some(number);
+          look.winner = won && progress >= 0.3;
+        },
+        Animation::Flip(pos, _) if pos == (row, col) => look.width = (progress * PI).cos().abs().max(0.05),
+        _ => {}
+      }
+    }
+    look
+  }
+}
+
+/// Adds steps turning cards over one after another, returning when the last one is done. Cards that
+/// don't move all change color at once.
+fn add_flips(steps: &mut Vec<Step>, board: &mut Board, flipped: &[(usize, usize)], color: CardColor, start: u64, moving: bool) -> u64 {
+  if flipped.is_empty() {
+    return start;
+  }
+  let (length, gap) = if moving { (FLIP_MILLIS, COMBO_MILLIS) } else { (0, 0) };
+  for (i, &(row, col)) in flipped.iter().enumerate() {
+    steps.push(Step {
+      start: start + i as u64 * gap,
+      length: length,
+      animation: Animation::Flip((row, col), color)
+    });
+    if let Space::Card(ref mut c) = *board.space_mut(row, col) {
+      c.card.color = color;
+    }
+  }
+  start + (flipped.len() as u64 - 1) * gap + length
+}
+
+/// Runs the battles for a card just placed, and starts showing the placement. With animations off,
+/// only the battles are shown.
+fn run_battles_shown(board: &mut Board, row: usize, col: usize, first: Option<(usize, usize)>, animate: bool, animations: &mut Option<Animations>) -> PlacementReport {
+  let mut before = board.clone();
+  let card = before.remove_card(row, col).expect("The card was just placed");
+  let report = board.run_battles_reported(row, col, first, &mut thread_rng());
+  *animations = Animations::new(&before, card, &report, animate);
+  report
+}
+
+/// A click on the match, which waits while animations play.
+enum Input {
+  /// A card in the hand of the player to move.
+  Hand(usize),
+  /// A space on the board, by row and column starting at 0.
+  Space(usize, usize)
+}
+
+struct MatchOutcome {
+  headline: &'static str,
+  detail: String,
+  /// Cards the player may pick from as their winnings.
+  choices: Vec<(usize, usize)>
+}
+
 fn find_assets() -> std::path::PathBuf {
   find_folder::Search::KidsThenParents(5, 2)
   .of(std::env::current_exe().unwrap().parent().unwrap().to_path_buf())
@@ -40,6 +615,116 @@ fn find_assets() -> std::path::PathBuf {
   .unwrap()
 }
 
+/// Loads the player's collection. A new player is given a set of starter cards.
+fn load_collection(path: &Path) -> Collection {
+  match Collection::load(path) {
+    Ok(c) => c,
+    Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+      let mut collection = Collection::new();
+      fill_collection(&mut collection);
+      save_collection(path, &collection);
+      collection
+    },
+    Err(e) => panic!("Could not load collection from {}: {}", path.display(), e)
+  }
+}
+
+/// Gives a player without enough cards to play a new set of starter cards.
+///
+/// Cards lost in a match stay lost, so a player who has lost too many only gets new ones by asking.
+fn fill_collection(collection: &mut Collection) {
+  if collection.len() >= HAND_SIZE {
+    return;
+  }
+  while collection.len() < STARTER_SIZE {
+    collection.add(random::random_collected_card());
+  }
+}
+
+/// Hands the cards at the given positions to the winner, updating the player's collection.
+///
+/// Returns the names of the cards that changed hands.
+fn transfer_cards(positions: &[(usize, usize)], sources: &mut HashMap<(usize, usize), CardSource>, collection: &mut Collection) -> Vec<String> {
+  let mut names = Vec::new();
+  let mut lost = Vec::new();
+  for pos in positions {
+    match sources.remove(pos) {
+      Some(CardSource::Opponent(card)) => {
+        names.push(card.card.to_string());
+        if !collection.add(card) {
+          println!("Collection is full, so the card was discarded");
+        }
+      },
+      Some(CardSource::Collection(i)) => {
+        names.push(collection.cards[i].card.to_string());
+        lost.push(i);
+      },
+      None => {}
+    }
+  }
+  lost.sort();
+  for i in lost.into_iter().rev() {
+    collection.remove(i);
+  }
+  names
+}
+
+fn save_collection(path: &Path, collection: &Collection) {
+  if let Some(parent) = path.parent() {
+    if let Err(e) = std::fs::create_dir_all(parent) {
+      println!("Could not create {}: {}", parent.display(), e);
+      return;
+    }
+  }
+  if let Err(e) = collection.save(path) {
+    println!("Could not save collection to {}: {}", path.display(), e);
+  }
+}
+
+/// Finds where the `i`th card of a hand of `len` cards goes, and how much it is scaled by.
+///
+/// Cards are laid out right to left across the top of the window and then down its right side,
+/// shrinking until the whole hand fits.
+fn hand_slot(i: usize, len: usize, window: [f64; 2]) -> (f64, f64, f64) {
+  let mut scale = 1.0;
+  let across = loop {
+    let across = (300.0 / (100.0 * scale)) as usize;
+    let down = (652.0 / (148.0 * scale)) as usize;
+    if across + down > len || scale <= 0.25 {
+      break across;
+    }
+    scale -= 0.05;
+  };
+  let (k, j) = if i < across {
+    (across - 1 - i, 0)
+  } else {
+    (0, i - across + 1)
+  };
+  let x = (window[0] / 2.0) - 2.0 - 50.0 * scale - (k as f64 * 100.0 * scale);
+  let y = (window[1] / 2.0) - 2.0 - 74.0 * scale - (j as f64 * 148.0 * scale);
+  (x, y, scale)
+}
+
+/// Finds where the `i`th card of the hand of the player who isn't moving goes, and how much it is
+/// scaled by.
+///
+/// Cards are laid out left to right below the new game button, shrinking until the whole hand fits
+/// in the corner left of the other hand.
+fn waiting_slot(i: usize, len: usize, window: [f64; 2]) -> (f64, f64, f64) {
+  let mut scale = 0.5;
+  let across = loop {
+    let across = (200.0 / (100.0 * scale)) as usize;
+    let down = (112.0 / (148.0 * scale)) as usize;
+    if across * down >= len || scale <= 0.25 {
+      break across;
+    }
+    scale -= 0.05;
+  };
+  let x = -(window[0] / 2.0) + 2.0 + 50.0 * scale + ((i % across) as f64 * 100.0 * scale);
+  let y = (window[1] / 2.0) - 36.0 - 74.0 * scale - ((i / across) as f64 * 148.0 * scale);
+  (x, y, scale)
+}
+
 fn main() {
   let endpoint = rodio::get_default_endpoint().unwrap();
 
@@ -69,13 +754,28 @@ fn main() {
 
   widget_ids!(struct Ids {
     cards[],
+    waiting_cards[],
+    hand_cover,
     grid_spaces[],
+    battle_numbers[],
     arrows[],
     win_text,
     win_rect,
     volume_slider,
     play_pause_button,
-    new_button
+    new_button,
+    animate_button,
+    deck_cards[],
+    deck_title,
+    deck_prev,
+    deck_next,
+    deck_start,
+    deck_starter,
+    deck_stakes,
+    deck_size,
+    deck_hands,
+    win_detail,
+    win_detail_rect
   });
   let mut ids = Ids::new(ui.widget_id_generator());
 
@@ -96,13 +796,46 @@ fn main() {
     southwest: image_map.insert(load_image("images/southwest.png", &display))
   };
 
+  let collection_path = tetra_master::collection::default_path();
+  let mut collection = load_collection(&collection_path);
+  let mut selected: Vec<usize> = Vec::new();
+  // The cards blue picked in a hot-seat match, while red picks theirs.
+  let mut blue_selected: Option<Vec<usize>> = None;
+  // Whether each player's hand is hidden while the other moves.
+  let mut closed_hands = true;
+  // Whether the hand of the player to move is hidden until they're at the computer, in a hot-seat
+  // match with closed hands.
+  let mut hand_covered = false;
+  let mut deck_page = 0;
+  let mut stakes = Stakes::default();
+  let mut board_size = BOARD_SIZES.iter().position(|&(w, h)| w == 4 && h == 4).unwrap();
+  let mut screen = Screen::DeckSelect;
+
   let mut board = Board::generate();
-  let mut player_hand: Vec<OwnedCard> = (0..5).map(|_| OwnedCard::blue(random::random_card())).collect();
-  let mut opponent_hand: Vec<OwnedCard> = (0..5).map(|_| OwnedCard::red(random::random_card())).collect();
+  let mut player_hand: Vec<OwnedCard> = Vec::new();
+  let mut player_sources: Vec<usize> = Vec::new();
+  let mut opponent_hand: Vec<OwnedCard> = Vec::new();
+  let mut opponent_sources: Vec<CollectedCard> = Vec::new();
+  let mut sources: HashMap<(usize, usize), CardSource> = HashMap::new();
+  let mut outcome: Option<MatchOutcome> = None;
 
   let mut is_player_turn = thread_rng().gen_weighted_bool(2);
+  let mut player_color = CardColor::Blue;
+  let (mut opponent, incoming) = opponent_from_args();
+  // Set when a bot can't carry on, so the computer takes over from it.
+  let mut bot_failed = false;
+  let mut spectators = Spectators::new();
+  // Connections made while the opponent's connection is lost, which may be them coming back.
+  let mut returning: Vec<Connection> = Vec::new();
+  // The cards the player can pick from to battle first.
+  let mut battle_choices: Vec<(usize, usize)> = Vec::new();
 
   let mut clicked_card: Option<usize> = None;
+  // The last placement, while it is being animated and the board on screen hasn't caught up with
+  // the match.
+  let mut animations: Option<Animations> = None;
+  let mut animate = true;
+  let mut inputs: VecDeque<Input> = VecDeque::new();
 
   let mut last_update = std::time::Instant::now();
   let mut ui_needs_update = true;
@@ -115,7 +848,7 @@ fn main() {
 
     let mut events: Vec<_> = display.poll_events().collect();
 
-    if events.is_empty() && !ui_needs_update {
+    if events.is_empty() && !ui_needs_update && animations.is_none() {
       events.extend(display.wait_events().next());
     }
 
@@ -133,7 +866,314 @@ fn main() {
       }
     }
 
-    if !is_player_turn {
+    if animations.as_ref().map(Animations::is_done).unwrap_or(false) {
+      animations = None;
+    }
+
+    // The first to connect is the opponent, unless they only want to watch. Everyone after that can
+    // only watch.
+    while let Some(accepted) = incoming.as_ref().and_then(|rx| rx.try_recv().ok()) {
+      let connection = match accepted {
+        Ok(c) => c,
+        Err(e) => {
+          println!("Could not accept a connection: {}", e);
+          continue;
+        }
+      };
+      let (width, height) = BOARD_SIZES[board_size];
+      match opponent {
+        Opponent::Waiting => {
+          let mut remote = Remote::new(connection, true);
+          remote.send(&Message::Hello(PROTOCOL_VERSION, Rules::sized(width, height)));
+          opponent = Opponent::Remote(remote);
+          ui_needs_update = true;
+        },
+        // Someone coming back to the match speaks first.
+        Opponent::Remote(ref remote) if remote.lost.is_some() => returning.push(connection),
+        _ => spectators.add(connection, Rules::sized(width, height))
+      }
+    }
+
+    if let Opponent::Remote(ref mut remote) = opponent {
+      // The host takes back a player who lost their connection, if they have the match's token.
+      let mut i = 0;
+      while i < returning.len() {
+        let message = match returning[i].try_recv() {
+          Some(m) => m,
+          None => {
+            i += 1;
+            continue;
+          }
+        };
+        let mut connection = returning.remove(i);
+        let resumed = match (message, remote.hosted.as_ref()) {
+          (Message::Resume(version, _, _), _) if version != PROTOCOL_VERSION => Err(format!("version {} is needed", PROTOCOL_VERSION)),
+          (Message::Resume(_, token, placements), Some(hosted)) => if Some(token) == remote.token {
+            hosted.resume(placements)
+          } else {
+            Err("that is not this match's token".to_string())
+          },
+          (Message::Watch(_), hosted) => {
+            spectators.watch(connection, hosted.map(|h| &h.state));
+            continue;
+          },
+          (Message::Bye, _) => continue,
+          _ => Err("a match is being played".to_string())
+        };
+        match resumed {
+          Ok(messages) => {
+            println!("The other player is back");
+            remote.connection = connection;
+            remote.lost = None;
+            for message in &messages {
+              remote.send(message);
+            }
+            ui_needs_update = true;
+          },
+          Err(reason) => {
+            let _ = connection.send(&Message::Illegal(reason));
+          }
+        }
+      }
+      // A player who joined keeps trying to get back to the host.
+      let mut reconnected = None;
+      if let Some(ref mut lost) = remote.lost {
+        if !remote.is_host {
+          let attempted = lost.attempt.as_ref().map(|rx| rx.try_recv());
+          match attempted {
+            Some(Ok(Ok(connection))) => reconnected = Some(connection),
+            Some(Ok(Err(_))) => lost.attempt = None,
+            Some(Err(_)) => {},
+            None => if Instant::now() >= lost.retry {
+              let address = remote.address.clone().expect("Only players who joined reconnect");
+              lost.attempt = Some(net::connect_in_background(address));
+              lost.retry = Instant::now() + Duration::from_secs(RECONNECT_WAIT);
+            }
+          }
+        }
+      }
+      if let Some(mut connection) = reconnected {
+        let placements = remote.checker.as_ref().map(RollChecker::placements).unwrap_or(0);
+        let token = remote.token.clone().unwrap_or_default();
+        let _ = connection.send(&Message::Resume(PROTOCOL_VERSION, token, placements));
+        if let Some(ref checker) = remote.checker {
+          let _ = connection.send(&checker.commitment());
+        }
+        println!("Reconnected to the host");
+        remote.connection = connection;
+        remote.lost = None;
+        ui_needs_update = true;
+      }
+    }
+
+    let mut new_state: Option<GameState> = None;
+    let mut disconnected = false;
+    let mut watching = false;
+    if let Opponent::Remote(ref mut remote) = opponent {
+      let expired = remote.lost.as_ref().map(|l| l.since.elapsed() >= Duration::from_secs(RECONNECT_GRACE)).unwrap_or(false);
+      if expired {
+        disconnected = true;
+      }
+      while let Some(message) = remote.try_recv() {
+        ui_needs_update = true;
+        let cheated = match remote.checker {
+          Some(ref mut checker) => checker.observe(&message).err(),
+          None => None
+        };
+        if let Some(reason) = cheated {
+          println!("The host cheated: {}", reason);
+          remote.send(&Message::Bye);
+          disconnected = true;
+          break;
+        }
+        match (remote.is_host, message) {
+          (false, Message::Hello(version, rules)) => {
+            let size = BOARD_SIZES.iter().position(|&(w, h)| w == rules.width && h == rules.height);
+            match size {
+              Some(size) if version == PROTOCOL_VERSION && rules.hand_size == Board::hand_size(rules.width, rules.height) => {
+                board_size = size;
+                selected.clear();
+                remote.joined = false;
+                remote.checker = None;
+                screen = Screen::DeckSelect;
+              },
+              _ => {
+                println!("The host's game can't be played here");
+                remote.send(&Message::Bye);
+                disconnected = true;
+                break;
+              }
+            }
+          },
+          (true, Message::Watch(_)) if remote.hosted.is_none() => {
+            watching = true;
+            break;
+          },
+          (true, Message::Join(version, hand)) => {
+            let (width, height) = BOARD_SIZES[board_size];
+            let hand_size = Board::hand_size(width, height);
+            if version != PROTOCOL_VERSION {
+              remote.send(&Message::Illegal(format!("version {} is needed", PROTOCOL_VERSION)));
+            } else if hand.len() != hand_size {
+              remote.send(&Message::Illegal(format!("{} cards are needed", hand_size)));
+            } else {
+              remote.hand = Some(hand);
+            }
+          },
+          (false, Message::Start(color, state)) => {
+            let seeds = SeedChain::new(state.board.width() * state.board.height(), &mut thread_rng());
+            let checker = RollChecker::new(seeds, state.clone());
+            remote.send(&checker.commitment());
+            remote.checker = Some(checker);
+            player_color = color;
+            stakes = Stakes::Practice;
+            sources.clear();
+            outcome = None;
+            clicked_card = None;
+            battle_choices.clear();
+            inputs.clear();
+            animations = None;
+            screen = Screen::Match;
+            new_state = Some(state);
+          },
+          (true, Message::Move(mv, _)) => {
+            let played = match remote.hosted {
+              Some(ref mut hosted) => hosted.play(player_color.opponent(), mv),
+              None => Err("no match is being played".to_string())
+            };
+            remote.share(false, played, &mut spectators);
+            new_state = remote.hosted.as_ref().map(|h| h.state.clone());
+          },
+          (true, Message::First(first)) => {
+            let played = match remote.hosted {
+              Some(ref mut hosted) => hosted.choose(player_color.opponent(), first),
+              None => Err("no match is being played".to_string())
+            };
+            remote.share(false, played, &mut spectators);
+            new_state = remote.hosted.as_ref().map(|h| h.state.clone());
+          },
+          (true, Message::Commit(commitment)) => {
+            let committed = match remote.hosted {
+              Some(ref mut hosted) => hosted.commit(commitment),
+              None => Err("no match is being played".to_string())
+            };
+            if let Err(reason) = committed {
+              remote.send(&Message::Illegal(reason));
+            }
+          },
+          (true, Message::Seed(placement, seed)) => {
+            let played = match remote.hosted {
+              Some(ref mut hosted) => hosted.reveal(placement, seed),
+              None => Err("no match is being played".to_string())
+            };
+            remote.share(false, played, &mut spectators);
+            new_state = remote.hosted.as_ref().map(|h| h.state.clone());
+          },
+          // The host has played, and the checker has noted the move and the card it battles first,
+          // so neither can change and it's safe to reveal a seed for it.
+          (false, Message::Move(..)) => {
+            let seed = remote.checker.as_ref().and_then(RollChecker::next_seed);
+            if let Some(seed) = seed {
+              remote.send(&seed);
+            }
+          },
+          (false, Message::Choose(targets)) => battle_choices = targets,
+          (false, message @ Message::Placed(..))
+          | (false, message @ Message::Battle(_))
+          | (false, message @ Message::Taken(_)) => remote.reported.push(message),
+          (false, Message::State(state)) => new_state = Some(state),
+          (_, Message::Illegal(reason)) => println!("The other player refused: {}", reason),
+          (_, Message::Bye) => {
+            if remote.can_resume() {
+              println!("The connection to the other player was lost. Waiting {} seconds for it to come back", RECONNECT_GRACE);
+              remote.lost = Some(Lost::new());
+            } else {
+              disconnected = true;
+            }
+            break;
+          },
+          (false, Message::Token(token)) => remote.token = Some(token),
+          // Only the host says what happened, and the state it sends after a move says everything
+          // the board needs.
+          _ => {}
+        }
+      }
+    }
+    if watching {
+      // The opponent only wanted to watch, so wait for someone else to play.
+      if let Opponent::Remote(remote) = std::mem::replace(&mut opponent, Opponent::Waiting) {
+        spectators.watch(remote.connection, None);
+      }
+    }
+    {
+      let hosted = match opponent {
+        Opponent::Remote(ref remote) => remote.hosted.as_ref().map(|h| &h.state),
+        _ => None
+      };
+      spectators.update(hosted);
+    }
+    if disconnected {
+      println!("The other player left");
+      returning.clear();
+      // A host waits for another player.
+      opponent = if incoming.is_some() { Opponent::Waiting } else { Opponent::Computer };
+      selected.clear();
+      battle_choices.clear();
+      screen = Screen::DeckSelect;
+    }
+    if let Some(state) = new_state {
+      animations = match opponent {
+        Opponent::Remote(ref mut remote) => Animations::from_messages(&board, &mut remote.reported, animate),
+        _ => None
+      };
+      is_player_turn = show_state(&state, player_color, &mut board, &mut player_hand, &mut opponent_hand);
+    }
+    if screen == Screen::Match && !is_player_turn && animations.is_none() {
+      if let Opponent::Bot(ref mut bot) = opponent {
+        let state = local_state(&board, &player_hand, &opponent_hand, CardColor::Red);
+        if state.hand(CardColor::Red).is_empty() {
+          is_player_turn = true;
+        } else {
+          bot.think(state);
+          match bot.poll() {
+            Some(Ok((mv, first))) => {
+              is_player_turn = true;
+              let card = opponent_hand.remove(mv.card);
+              let source = opponent_sources.remove(mv.card);
+              let played = card.card;
+              board.add_card(mv.row, mv.column, card);
+              sources.insert((mv.row, mv.column), CardSource::Opponent(source));
+              let report = run_battles_shown(&mut board, mv.row, mv.column, first, animate, &mut animations);
+              if let Err(e) = bot.tell(CardColor::Red, mv, played, &report) {
+                println!("The bot stopped playing because {}", e);
+                bot_failed = true;
+              }
+            },
+            Some(Err(e)) => {
+              println!("The bot stopped playing because {}", e);
+              bot_failed = true;
+            },
+            // Keep drawing while the bot thinks.
+            None => ui_needs_update = true
+          }
+        }
+      }
+    }
+    if bot_failed {
+      // The computer plays in the bot's place from now on.
+      bot_failed = false;
+      opponent = Opponent::Computer;
+    }
+    let against_computer = match opponent {
+      Opponent::Computer => true,
+      _ => false
+    };
+    let hot_seat = match opponent {
+      Opponent::Local => true,
+      _ => false
+    };
+
+    if screen == Screen::Match && !is_player_turn && against_computer && animations.is_none() {
       let mut do_opponent_turn = || {
         is_player_turn = true;
         if opponent_hand.is_empty() {
@@ -141,9 +1181,10 @@ fn main() {
         }
         let i = thread_rng().gen_range(0, opponent_hand.len());
         let card = opponent_hand.remove(i);
+        let source = opponent_sources.remove(i);
         let mut empty = Vec::new();
-        for r in 0..4 {
-          for c in 0..4 {
+        for r in 0..board.height() {
+          for c in 0..board.width() {
             if let Space::Empty = *board.space(r + 1, c + 1) {
               empty.push((r, c));
             }
@@ -157,7 +1198,8 @@ fn main() {
           }
         };
         board.add_card(r + 1, c + 1, card);
-        board.run_battles_pos(r + 1, c + 1);
+        sources.insert((r + 1, c + 1), CardSource::Opponent(source));
+        run_battles_shown(&mut board, r + 1, c + 1, None, animate, &mut animations);
       };
       do_opponent_turn();
     }
@@ -187,138 +1229,579 @@ fn main() {
         }
       }
 
-      let new_game_events = widget::Button::new()
-        .label("New game")
-        .w_h(96.0, 32.0)
-        .top_left_with_margins_on(ui.window, 2.0, 16.0)
-        .set(ids.new_button, ui);
-      for _ in new_game_events {
-        board = Board::generate();
-        player_hand = (0..5).map(|_| OwnedCard::blue(random::random_card())).collect();
-        opponent_hand = (0..5).map(|_| OwnedCard::red(random::random_card())).collect();
-        is_player_turn = thread_rng().gen_weighted_bool(2);
-      }
-
-      ids.cards.resize(player_hand.len(), &mut ui.widget_id_generator());
-      for (i, card) in player_hand.iter().enumerate() {
-        let (x, y) = match i {
-          0 => (
-            -(ui.window_dim()[0] / 2.0) + 250.0 + 2.0,
-            (ui.window_dim()[1] / 2.0) - 74.0 - 2.0
-          ),
-          1 => (
-            -(ui.window_dim()[0] / 2.0) + 350.0 + 2.0,
-            (ui.window_dim()[1] / 2.0) - 74.0 - 2.0
-          ),
-          2 => (
-            -(ui.window_dim()[0] / 2.0) + 450.0 + 2.0,
-            (ui.window_dim()[1] / 2.0) - 74.0 - 2.0
-          ),
-          3 => (
-            -(ui.window_dim()[0] / 2.0) + 450.0 + 2.0,
-            (ui.window_dim()[1] / 2.0) - 222.0 - 2.0
-          ),
-          4 => (
-            -(ui.window_dim()[0] / 2.0) + 450.0 + 2.0,
-            (ui.window_dim()[1] / 2.0) - 370.0 - 2.0
-          ),
-          _ => panic!("Hand too large")
+      if screen == Screen::DeckSelect {
+        let (width, height) = BOARD_SIZES[board_size];
+        let hand_size = Board::hand_size(width, height);
+        let status = match opponent {
+          Opponent::Computer | Opponent::Bot(_) | Opponent::Local => "",
+          Opponent::Waiting => " - waiting for a player",
+          Opponent::Remote(ref r) if r.is_host && r.hand.is_none() => " - waiting for their cards",
+          Opponent::Remote(ref r) if !r.is_host && r.joined => " - waiting for the host",
+          Opponent::Remote(_) => ""
+        };
+        let chooser = match (hot_seat, blue_selected.is_some()) {
+          (false, _) => "Choose your",
+          (true, false) => "Blue: choose your",
+          (true, true) => "Red: choose your"
         };
-        let card_id = *ids.cards.get(i).unwrap();
-        let label = &card.to_string();
-        let (card_id, arrows, mut card_button) = owned_card_to_game_card(&images, card_id, widget::Button::new(), card);
-        card_button = card_button
-          .label(label)
-          .x_y(x, y);
+        let title = format!("{} cards ({}/{}){}", chooser, selected.len(), hand_size, status);
+        widget::Text::new(&title)
+          .font_size(24)
+          .color(conrod::color::WHITE)
+          .mid_top_with_margin_on(ui.window, 64.0)
+          .set(ids.deck_title, ui);
 
-        for _click in card_button.clone().set(card_id, ui) {
-          clicked_card = Some(i);
+        let pages = (collection.len() + DECK_PAGE_SIZE - 1) / DECK_PAGE_SIZE;
+        let next_events = widget::Button::new()
+          .label("Next")
+          .w_h(64.0, 32.0)
+          .top_right_with_margins_on(ui.window, 2.0, 16.0)
+          .set(ids.deck_next, ui);
+        for _ in next_events {
+          if deck_page + 1 < pages {
+            deck_page += 1;
+          }
         }
-
-        let amount_of_arrows = ids.arrows.len();
-        ids.arrows.resize(amount_of_arrows + arrows.len(), &mut ui.widget_id_generator());
-        for (i, arrow) in arrows.into_iter().enumerate() {
-          arrow.set(*ids.arrows.get(amount_of_arrows + i).unwrap(), ui);
+        let prev_events = widget::Button::new()
+          .label("Prev")
+          .w_h(64.0, 32.0)
+          .left_from(ids.deck_next, 8.0)
+          .set(ids.deck_prev, ui);
+        for _ in prev_events {
+          if deck_page > 0 {
+            deck_page -= 1;
+          }
         }
-      }
 
-      ids.grid_spaces.resize(16, &mut ui.widget_id_generator());
-      let mut id_count = 0;
-      for row in 0..4 {
-        for col in 0..4 {
-          let x: f64 = -(ui.window_dim()[0] / 2.0) + (col as f64 * 100.0) + 52.0;
-          let y: f64 = (ui.window_dim()[1] / 2.0) - (row as f64 * 148.0) - 224.0;
-          let button = widget::Button::new();
-          let mut button_id = *ids.grid_spaces.get(id_count).unwrap();
-          let (label, arrows, mut button) = match *board.space(row + 1, col + 1) {
-            Space::Block => (String::new(), Vec::new(), button.color(conrod::color::DARK_GRAY)),
-            Space::Card(ref c) => {
-              let (id, arrows, button) = owned_card_to_game_card(&images, button_id, button, c);
-              button_id = id;
-              (c.to_string(), arrows, button)
-            },
-            Space::Empty => (String::new(), Vec::new(), button.color(conrod::color::BLACK))
-          };
-          let label = &label;
-          button = button
+        let first = deck_page * DECK_PAGE_SIZE;
+        let last = min(first + DECK_PAGE_SIZE, collection.len());
+        ids.deck_cards.resize(DECK_PAGE_SIZE, &mut ui.widget_id_generator());
+        for (slot, i) in (first..last).enumerate() {
+          let x = -(ui.window_dim()[0] / 2.0) + ((slot % 5) as f64 * 100.0) + 52.0;
+          let y = (ui.window_dim()[1] / 2.0) - ((slot / 5) as f64 * 148.0) - 224.0;
+          let card = OwnedCard::blue(collection.cards[i].card);
+          let card_id = *ids.deck_cards.get(slot).unwrap();
+          let label = &card.to_string();
+          let (card_id, arrows, mut card_button) = owned_card_to_game_card(&images, card_id, widget::Button::new(), &card);
+          card_button = card_button
             .label(label)
-            .w_h(100.0, 148.0)
-            .border_color(conrod::color::WHITE)
             .x_y(x, y);
-          for _click in button.set(button_id, ui) {
-            if let Some(i) = clicked_card {
-              if let Space::Empty = *board.space(row + 1, col + 1) {
-                is_player_turn = false;
-                let card = player_hand.remove(i);
-                board.add_card(row + 1, col + 1, card);
-                board.run_battles_pos(row + 1, col + 1);
-                clicked_card = None;
-              }
+          if selected.contains(&i) {
+            card_button = card_button.color(conrod::color::YELLOW);
+          }
+
+          for _click in card_button.set(card_id, ui) {
+            if let Some(pos) = selected.iter().position(|&s| s == i) {
+              selected.remove(pos);
+            } else if selected.len() < hand_size {
+              selected.push(i);
             }
           }
+
           let amount_of_arrows = ids.arrows.len();
           ids.arrows.resize(amount_of_arrows + arrows.len(), &mut ui.widget_id_generator());
           for (i, arrow) in arrows.into_iter().enumerate() {
             arrow.set(*ids.arrows.get(amount_of_arrows + i).unwrap(), ui);
           }
-          id_count += 1;
+        }
+
+        // Cards never change hands between machines, so networked matches are for practice.
+        let stakes_events = widget::Button::new()
+          .label(if stakes == Stakes::Practice || !against_computer { "Practice" } else { "Capture" })
+          .w_h(96.0, 32.0)
+          .top_left_with_margins_on(ui.window, 2.0, 16.0)
+          .set(ids.deck_stakes, ui);
+        for _ in stakes_events {
+          if against_computer {
+            stakes = if stakes == Stakes::Practice { Stakes::Capture } else { Stakes::Practice };
+          }
+        }
+
+        let size_label = format!("{}x{}", width, height);
+        let size_events = widget::Button::new()
+          .label(&size_label)
+          .w_h(64.0, 32.0)
+          .right_from(ids.deck_stakes, 8.0)
+          .set(ids.deck_size, ui);
+        for _ in size_events {
+          // The host picks the board for networked matches.
+          if let Opponent::Remote(ref remote) = opponent {
+            if !remote.is_host {
+              continue;
+            }
+          }
+          board_size = (board_size + 1) % BOARD_SIZES.len();
+          selected.clear();
+          blue_selected = None;
+          if let Opponent::Remote(ref mut remote) = opponent {
+            let (width, height) = BOARD_SIZES[board_size];
+            remote.hand = None;
+            remote.send(&Message::Hello(PROTOCOL_VERSION, Rules::sized(width, height)));
+          }
+          let (width, height) = BOARD_SIZES[board_size];
+          spectators.send(&Message::Hello(PROTOCOL_VERSION, Rules::sized(width, height)));
+        }
+
+        let hands_events = widget::Button::new()
+          .label(if closed_hands { "Closed hands" } else { "Open hands" })
+          .w_h(128.0, 32.0)
+          .right_from(ids.deck_size, 8.0)
+          .set(ids.deck_hands, ui);
+        for _ in hands_events {
+          closed_hands = !closed_hands;
+        }
+
+        if collection.len() < HAND_SIZE {
+          let starter_events = widget::Button::new()
+            .label("Take starter cards")
+            .w_h(160.0, 32.0)
+            .bottom_left_with_margins_on(ui.window, 2.0, 120.0)
+            .set(ids.deck_starter, ui);
+          for _ in starter_events {
+            fill_collection(&mut collection);
+            save_collection(&collection_path, &collection);
+          }
+        }
+
+        let start_events = widget::Button::new()
+          .label("Start")
+          .w_h(96.0, 32.0)
+          .bottom_left_with_margins_on(ui.window, 2.0, 16.0)
+          .set(ids.deck_start, ui);
+        for _ in start_events {
+          if selected.len() != hand_size {
+            continue;
+          }
+          let cards: Vec<Card> = selected.iter().map(|&i| collection.cards[i].card).collect();
+          match opponent {
+            Opponent::Computer | Opponent::Bot(_) => {},
+            // Blue picks first, then hands the computer to red.
+            Opponent::Local if blue_selected.is_none() => {
+              blue_selected = Some(selected.clone());
+              selected.clear();
+              continue;
+            },
+            Opponent::Local => {},
+            Opponent::Waiting => continue,
+            Opponent::Remote(ref mut remote) => {
+              if !remote.is_host {
+                remote.send(&Message::Join(PROTOCOL_VERSION, cards));
+                remote.joined = true;
+                continue;
+              }
+              let hand = match remote.hand.take() {
+                Some(h) => h,
+                None => continue
+              };
+              let to_move = if thread_rng().gen() { CardColor::Blue } else { CardColor::Red };
+              let state = GameState::new(Board::generate_sized(width, height), cards, hand, to_move);
+              remote.send(&Message::Start(CardColor::Red, state.clone()));
+              spectators.send(&Message::State(state.clone()));
+              player_color = CardColor::Blue;
+              is_player_turn = show_state(&state, player_color, &mut board, &mut player_hand, &mut opponent_hand);
+              let seeds = SeedChain::new(width * height, &mut thread_rng());
+              let hosted = HostedMatch::new(state, CardColor::Red, seeds);
+              let token = net::new_token(&mut thread_rng());
+              remote.send(&hosted.commitment());
+              remote.send(&Message::Token(token.clone()));
+              remote.hosted = Some(hosted);
+              remote.token = Some(token);
+              stakes = Stakes::Practice;
+              sources.clear();
+              outcome = None;
+              clicked_card = None;
+              battle_choices.clear();
+              inputs.clear();
+              animations = None;
+              screen = Screen::Match;
+              continue;
+            }
+          }
+          board = Board::generate_sized(width, height);
+          player_color = CardColor::Blue;
+          match blue_selected.take() {
+            Some(blue) => {
+              player_sources = blue;
+              opponent_sources = selected.iter().map(|&i| collection.cards[i].clone()).collect();
+              // Both players' cards are from this collection, so neither can take the other's.
+              stakes = Stakes::Practice;
+            },
+            None => {
+              player_sources = selected.clone();
+              opponent_sources = (0..hand_size).map(|_| random::random_collected_card()).collect();
+            }
+          }
+          player_hand = player_sources.iter().map(|&i| OwnedCard::blue(collection.cards[i].card)).collect();
+          opponent_hand = opponent_sources.iter().map(|c| OwnedCard::red(c.card)).collect();
+          sources.clear();
+          outcome = None;
+          is_player_turn = thread_rng().gen_weighted_bool(2);
+          hand_covered = hot_seat && closed_hands;
+          clicked_card = None;
+          battle_choices.clear();
+          inputs.clear();
+          animations = None;
+          screen = Screen::Match;
+          if let Opponent::Bot(ref mut bot) = opponent {
+            let to_move = if is_player_turn { CardColor::Blue } else { CardColor::Red };
+            if let Err(e) = bot.start(&local_state(&board, &player_hand, &opponent_hand, to_move)) {
+              println!("The bot stopped playing because {}", e);
+              bot_failed = true;
+            }
+          }
         }
       }
 
-      if opponent_hand.is_empty() && player_hand.is_empty() {
-        let cards: Vec<&PlacedCard> = board.spaces
-          .iter()
-          .flat_map(|x| x.iter().collect::<Vec<&Space>>())
-          .filter(|s| s.is_card())
-          .map(|x| match *x {
-            Space::Card(ref c) => c,
-            _ => unreachable!()
-          })
-          .collect();
-        let score = cards.iter().fold(Score::default(), |s, &x| s + x);
-        let text = if score.blue == score.red {
-          "Draw"
-        } else if score.blue > score.red {
-          "Blue wins"
+      if screen == Screen::Match {
+        let new_game_events = widget::Button::new()
+          .label("New game")
+          .w_h(96.0, 32.0)
+          .top_left_with_margins_on(ui.window, 2.0, 16.0)
+          .set(ids.new_button, ui);
+        for _ in new_game_events {
+          selected.clear();
+          blue_selected = None;
+          deck_page = 0;
+          battle_choices.clear();
+          inputs.clear();
+          animations = None;
+          screen = Screen::DeckSelect;
+          // The host starts over with the same player, or waits for a new one if the player's
+          // connection was lost. A player who joined leaves instead.
+          let mut leave = false;
+          if let Opponent::Remote(ref mut remote) = opponent {
+            let (width, height) = BOARD_SIZES[board_size];
+            if remote.is_host && remote.lost.is_none() {
+              remote.hosted = None;
+              remote.hand = None;
+              remote.token = None;
+              remote.send(&Message::Hello(PROTOCOL_VERSION, Rules::sized(width, height)));
+            } else {
+              remote.send(&Message::Bye);
+              leave = true;
+            }
+            spectators.send(&Message::Hello(PROTOCOL_VERSION, Rules::sized(width, height)));
+          }
+          if leave {
+            returning.clear();
+            opponent = if incoming.is_some() { Opponent::Waiting } else { Opponent::Computer };
+          }
+        }
+
+        let animate_events = widget::Button::new()
+          .label(if animate { "Animations" } else { "No animations" })
+          .w_h(128.0, 32.0)
+          .right_from(ids.new_button, 8.0)
+          .set(ids.animate_button, ui);
+        for _ in animate_events {
+          animate = !animate;
+          if !animate {
+            animations = None;
+          }
+        }
+
+        // In a hot-seat match the hand on the right belongs to whoever is moving.
+        let red_to_move = hot_seat && !is_player_turn;
+        let (hand, waiting_hand) = if red_to_move { (&opponent_hand, &player_hand) } else { (&player_hand, &opponent_hand) };
+        if hand_covered {
+          let cover_events = widget::Button::new()
+            .label(if red_to_move { "Red: show your cards" } else { "Blue: show your cards" })
+            .w_h(296.0, 144.0)
+            .top_right_with_margins_on(ui.window, 4.0, 4.0)
+            .set(ids.hand_cover, ui);
+          for _ in cover_events {
+            hand_covered = false;
+          }
         } else {
-          "Red wins"
-        };
-        let text_widget = widget::Text::new(text).font_size(48);
-        let x_dim = match text_widget.default_x_dimension(ui) {
-          conrod::position::Dimension::Absolute(x) => x,
-          _ => panic!()
-        };
-        let y_dim = match text_widget.default_y_dimension(ui) {
-          conrod::position::Dimension::Absolute(x) => x,
-          _ => panic!()
-        };
-        widget::Rectangle::fill_with([x_dim + 8.0, y_dim + 8.0], conrod::color::BLACK)
-          .middle_of(ui.window)
-          .set(ids.win_rect, ui);
-        text_widget
-          .color(conrod::color::WHITE)
-          .middle_of(ids.win_rect)
-          .set(ids.win_text, ui);
+          ids.cards.resize(hand.len(), &mut ui.widget_id_generator());
+          for (i, card) in hand.iter().enumerate() {
+            let (x, y, scale) = hand_slot(i, hand.len(), ui.window_dim());
+            let card_id = *ids.cards.get(i).unwrap();
+            let label = &card.to_string();
+            let (card_id, arrows, mut card_button) = owned_card_to_game_card(&images, card_id, widget::Button::new(), card);
+            card_button = card_button
+              .label(label)
+              .w_h(96.0 * scale, 144.0 * scale)
+              .x_y(x, y);
+
+            for _click in card_button.clone().set(card_id, ui) {
+              inputs.push_back(Input::Hand(i));
+            }
+
+            let amount_of_arrows = ids.arrows.len();
+            ids.arrows.resize(amount_of_arrows + arrows.len(), &mut ui.widget_id_generator());
+            for (i, arrow) in arrows.into_iter().enumerate() {
+              arrow.set(*ids.arrows.get(amount_of_arrows + i).unwrap(), ui);
+            }
+          }
+        }
+
+        // With open hands, the cards of the player who isn't moving can be seen but not played.
+        if !closed_hands {
+          ids.waiting_cards.resize(waiting_hand.len(), &mut ui.widget_id_generator());
+          for (i, card) in waiting_hand.iter().enumerate() {
+            let (x, y, scale) = waiting_slot(i, waiting_hand.len(), ui.window_dim());
+            let card_id = *ids.waiting_cards.get(i).unwrap();
+            let label = &card.to_string();
+            let (card_id, arrows, card_button) = owned_card_to_game_card(&images, card_id, widget::Button::new(), card);
+            card_button
+              .label(label)
+              .w_h(96.0 * scale, 144.0 * scale)
+              .x_y(x, y)
+              .set(card_id, ui);
+
+            let amount_of_arrows = ids.arrows.len();
+            ids.arrows.resize(amount_of_arrows + arrows.len(), &mut ui.widget_id_generator());
+            for (i, arrow) in arrows.into_iter().enumerate() {
+              arrow.set(*ids.arrows.get(amount_of_arrows + i).unwrap(), ui);
+            }
+          }
+        }
+
+        // The board fits in the space a standard board takes up, below and left of the hand.
+        let scale = (4.0 / board.width() as f64).min(4.0 / board.height() as f64).min(1.0);
+        let (space_w, space_h) = (100.0 * scale, 148.0 * scale);
+        ids.grid_spaces.resize(board.width() * board.height(), &mut ui.widget_id_generator());
+        let mut id_count = 0;
+        // While a placement is animated, the board is shown as it was before the cards it flipped
+        // turned over.
+        let shown_board = animations.as_ref().map(Animations::board);
+        // The numbers of the battle being shown, to go over the two cards.
+        let mut numbers = Vec::new();
+        for row in 0..board.height() {
+          for col in 0..board.width() {
+            let x: f64 = -(ui.window_dim()[0] / 2.0) + (col as f64 * space_w) + space_w / 2.0 + 2.0;
+            let y: f64 = (ui.window_dim()[1] / 2.0) - (row as f64 * space_h) - space_h / 2.0 - 150.0;
+            let button = widget::Button::new();
+            let mut button_id = *ids.grid_spaces.get(id_count).unwrap();
+            let space = match shown_board {
+              Some(ref b) => b.space(row + 1, col + 1).clone(),
+              None => board.space(row + 1, col + 1).clone()
+            };
+            let look = match animations {
+              Some(ref a) => a.look(row + 1, col + 1),
+              None => Look::still()
+            };
+            let (label, arrows, mut button) = match space {
+              Space::Block => (String::new(), Vec::new(), button.color(conrod::color::DARK_GRAY)),
+              Space::Card(ref c) => {
+                let (id, arrows, mut button) = owned_card_to_game_card(&images, button_id, button, c);
+                button_id = id;
+                let is_choice = outcome.as_ref().map(|o| o.choices.contains(&(row + 1, col + 1))).unwrap_or(false)
+                  || battle_choices.contains(&(row + 1, col + 1));
+                if is_choice || look.winner {
+                  button = button.color(conrod::color::YELLOW);
+                }
+                (c.to_string(), arrows, button)
+              },
+              Space::Empty => (String::new(), Vec::new(), button.color(conrod::color::BLACK))
+            };
+            let label = &label;
+            button = button
+              .label(label)
+              .w_h(space_w * look.width, space_h)
+              .border_color(conrod::color::WHITE)
+              .x_y(x + look.offset.0, y + look.offset.1);
+            if let Some(number) = look.number {
+              numbers.push((button_id, number));
+            }
+            for _click in button.set(button_id, ui) {
+              inputs.push_back(Input::Space(row, col));
+            }
+            let amount_of_arrows = ids.arrows.len();
+            ids.arrows.resize(amount_of_arrows + arrows.len(), &mut ui.widget_id_generator());
+            for (i, arrow) in arrows.into_iter().enumerate() {
+              arrow.set(*ids.arrows.get(amount_of_arrows + i).unwrap(), ui);
+            }
+            id_count += 1;
+          }
+        }
+
+        // Clicks made while animations play wait until they're over, and are then handled in order.
+        while animations.is_none() {
+          let red_to_move = hot_seat && !is_player_turn;
+          match inputs.pop_front() {
+            Some(Input::Hand(i)) => {
+              let hand_len = if red_to_move { opponent_hand.len() } else { player_hand.len() };
+              if (is_player_turn || hot_seat) && i < hand_len {
+                clicked_card = Some(i);
+              }
+            },
+            Some(Input::Space(row, col)) => {
+              if battle_choices.contains(&(row + 1, col + 1)) {
+                let first = (row + 1, col + 1);
+                battle_choices.clear();
+                if let Opponent::Remote(ref mut remote) = opponent {
+                  if remote.is_host {
+                    let played = match remote.hosted {
+                      Some(ref mut hosted) => hosted.choose(player_color, first),
+                      None => Err("no match is being played".to_string())
+                    };
+                    battle_choices = remote.share(true, played, &mut spectators);
+                    if let Some(ref hosted) = remote.hosted {
+                      animations = Animations::from_messages(&board, &mut remote.reported, animate);
+                      is_player_turn = show_state(&hosted.state, player_color, &mut board, &mut player_hand, &mut opponent_hand);
+                    }
+                  } else {
+                    if let Some(ref mut checker) = remote.checker {
+                      checker.announce_first(first);
+                    }
+                    remote.send(&Message::First(first));
+                  }
+                }
+              } else if let Some(i) = clicked_card {
+                if let Space::Empty = *board.space(row + 1, col + 1) {
+                  clicked_card = None;
+                  if let Opponent::Remote(ref mut remote) = opponent {
+                    if !battle_choices.is_empty() {
+                      continue;
+                    }
+                    let mv = Move::new(i, row + 1, col + 1);
+                    if remote.is_host {
+                      let played = match remote.hosted {
+                        Some(ref mut hosted) => hosted.play(player_color, mv),
+                        None => Err("no match is being played".to_string())
+                      };
+                      battle_choices = remote.share(true, played, &mut spectators);
+                      if let Some(ref hosted) = remote.hosted {
+                        animations = Animations::from_messages(&board, &mut remote.reported, animate);
+                        is_player_turn = show_state(&hosted.state, player_color, &mut board, &mut player_hand, &mut opponent_hand);
+                      }
+                    } else {
+                      remote.send(&Message::Move(mv, None));
+                      if let Some(ref mut checker) = remote.checker {
+                        checker.announce(mv, None);
+                      }
+                      let seed = remote.checker.as_ref().and_then(RollChecker::next_seed);
+                      if let Some(seed) = seed {
+                        remote.send(&seed);
+                      }
+                    }
+                    continue;
+                  }
+                  if red_to_move {
+                    is_player_turn = true;
+                    let card = opponent_hand.remove(i);
+                    let source = opponent_sources.remove(i);
+                    board.add_card(row + 1, col + 1, card);
+                    sources.insert((row + 1, col + 1), CardSource::Opponent(source));
+                    run_battles_shown(&mut board, row + 1, col + 1, None, animate, &mut animations);
+                    hand_covered = closed_hands && !player_hand.is_empty();
+                    continue;
+                  }
+                  is_player_turn = false;
+                  hand_covered = hot_seat && closed_hands && !opponent_hand.is_empty();
+                  let card = player_hand.remove(i);
+                  let source = player_sources.remove(i);
+                  let played = card.card;
+                  board.add_card(row + 1, col + 1, card);
+                  sources.insert((row + 1, col + 1), CardSource::Collection(source));
+                  let report = run_battles_shown(&mut board, row + 1, col + 1, None, animate, &mut animations);
+                  if let Opponent::Bot(ref mut bot) = opponent {
+                    if let Err(e) = bot.tell(CardColor::Blue, Move::new(i, row + 1, col + 1), played, &report) {
+                      println!("The bot stopped playing because {}", e);
+                      bot_failed = true;
+                    }
+                  }
+                }
+              }
+              if let Some(ref mut outcome) = outcome {
+                if outcome.choices.contains(&(row + 1, col + 1)) {
+                  let names = transfer_cards(&[(row + 1, col + 1)], &mut sources, &mut collection);
+                  outcome.detail = format!("You take {}", names.join(", "));
+                  outcome.choices.clear();
+                  save_collection(&collection_path, &collection);
+                }
+              }
+            },
+            None => break
+          }
+        }
+
+        ids.battle_numbers.resize(numbers.len(), &mut ui.widget_id_generator());
+        for (i, &(space_id, number)) in numbers.iter().enumerate() {
+          widget::Text::new(&number.to_string())
+            .font_size(40)
+            .color(conrod::color::BLACK)
+            .middle_of(space_id)
+            .set(*ids.battle_numbers.get(i).unwrap(), ui);
+        }
+
+        if opponent_hand.is_empty() && player_hand.is_empty() && animations.is_none() {
+          if outcome.is_none() {
+            let winner = board.score().winner();
+            let mut result = MatchOutcome {
+              headline: match winner {
+                None => "Draw",
+                Some(CardColor::Blue) => "Blue wins",
+                Some(CardColor::Red) => "Red wins"
+              },
+              detail: String::new(),
+              choices: Vec::new()
+            };
+            let spoils = match winner {
+              Some(w) => stakes.spoils(&board, w),
+              None => Spoils::Nothing
+            };
+            let taken = match spoils {
+              Spoils::Nothing => Vec::new(),
+              Spoils::Perfect(all) => {
+                result.detail.push_str("Perfect! ");
+                all
+              },
+              Spoils::Choose(choices) => if winner == Some(CardColor::Blue) {
+                result.detail.push_str("Choose a card to take");
+                result.choices = choices;
+                Vec::new()
+              } else {
+                vec![*thread_rng().choose(&choices).unwrap()]
+              }
+            };
+            if !taken.is_empty() {
+              let names = transfer_cards(&taken, &mut sources, &mut collection);
+              let verb = if winner == Some(CardColor::Blue) { "You take" } else { "Red takes" };
+              result.detail.push_str(&format!("{} {}", verb, names.join(", ")));
+              save_collection(&collection_path, &collection);
+            }
+            outcome = Some(result);
+          }
+          let outcome = outcome.as_ref().unwrap();
+          let text_widget = widget::Text::new(outcome.headline).font_size(48);
+          let x_dim = match text_widget.default_x_dimension(ui) {
+            conrod::position::Dimension::Absolute(x) => x,
+            _ => panic!()
+          };
+          let y_dim = match text_widget.default_y_dimension(ui) {
+            conrod::position::Dimension::Absolute(x) => x,
+            _ => panic!()
+          };
+          widget::Rectangle::fill_with([x_dim + 8.0, y_dim + 8.0], conrod::color::BLACK)
+            .middle_of(ui.window)
+            .set(ids.win_rect, ui);
+          text_widget
+            .color(conrod::color::WHITE)
+            .middle_of(ids.win_rect)
+            .set(ids.win_text, ui);
+
+          if !outcome.detail.is_empty() {
+            let detail_widget = widget::Text::new(&outcome.detail).font_size(20);
+            let x_dim = match detail_widget.default_x_dimension(ui) {
+              conrod::position::Dimension::Absolute(x) => x,
+              _ => panic!()
+            };
+            let y_dim = match detail_widget.default_y_dimension(ui) {
+              conrod::position::Dimension::Absolute(x) => x,
+              _ => panic!()
+            };
+            widget::Rectangle::fill_with([x_dim + 8.0, y_dim + 8.0], conrod::color::BLACK)
+              .down_from(ids.win_rect, 0.0)
+              .set(ids.win_detail_rect, ui);
+            detail_widget
+              .color(conrod::color::WHITE)
+              .middle_of(ids.win_detail_rect)
+              .set(ids.win_detail, ui);
+          }
+        }
       }
     }
 
@@ -332,24 +1815,6 @@ fn main() {
   }
 }
 
-#[derive(Debug, Default)]
-struct Score {
-  blue: u8,
-  red: u8
-}
-
-impl<'a> std::ops::Add<&'a PlacedCard> for Score {
-  type Output = Score;
-
-  fn add(mut self, rhs: &'a PlacedCard) -> Score {
-    match rhs.color.get() {
-      CardColor::Blue => self.blue += 1,
-      CardColor::Red => self.red += 1
-    }
-    self
-  }
-}
-
 fn load_image(url: &str, display: &glium::Display) -> glium::texture::Texture2d {
   let assets = find_assets();
   let path = assets.join(url);
@@ -408,14 +1873,20 @@ fn owned_card_to_game_card<'a>(images: &ArrowImages, id: widget::Id, base: widge
     .label_y(conrod::position::Relative::Scalar(-48.0))
     .center_justify_label()
     .w_h(96.0, 144.0)
-    .color(if let tetra_master::Color::Blue = card.color.get() { conrod::color::LIGHT_BLUE } else { conrod::color::ORANGE });
+    .color(if let tetra_master::Color::Blue = card.color { conrod::color::LIGHT_BLUE } else { conrod::color::ORANGE });
   (id, arrows, button)
 }
 
 mod random {
   use tetra_master::*;
+  use tetra_master::collection::{CollectedCard, CARD_TYPES};
   use rand::{thread_rng, Rng};
 
+  /// Get a random card of a random type, for a player's collection.
+  pub fn random_collected_card() -> CollectedCard {
+    CollectedCard::new(thread_rng().gen_range(0, CARD_TYPES), random_card())
+  }
+
   /// Get a random card for a player.
   pub fn random_card() -> Card {
     let power = weighted_level();
@@ -0,0 +1,123 @@
+//! A Tetra Master engine that talks over standard input and output, so other programs can use the
+//! AI and the battle maths without linking to this library.
+//!
+//! Each command is one line of words separated by spaces. Boards, hands, cards and colors are
+//! written as in `notation`, and moves as the index of the card in hand, the row and the column.
+//!
+//! - `position <board> hands <blue hand> <red hand> <color>`: sets the match to search, with the
+//!   color to move. Replies nothing.
+//! - `go depth <n>`: searches `n` placements ahead, from 1 to 10. Replies `info depth <d> value
+//!   <value> nodes <nodes>` for each depth searched, then `bestmove <card> <row> <column>`, or
+//!   `bestmove none` if there is no move. `go` alone searches to the default depth.
+//! - `bestmove`: replies with the best move of the last search again.
+//! - `odds <attacker> <defender>`: replies `odds <attacker> <defender> <draw>` with the chance of
+//!   each result of a battle. Cards may leave out their arrows, like `1M23`.
+//! - `isready`: replies `readyok`.
+//! - `quit`: stops the engine.
+//!
+//! A command that can't be carried out replies `error <reason>`.
+
+extern crate tetra_master;
+
+use tetra_master::TetraMaster;
+use tetra_master::ai::{SearchResult, Searcher};
+use tetra_master::notation::{format_move, parse_board, parse_card, parse_color, parse_hand};
+use tetra_master::state::GameState;
+
+use std::io::{stdin, BufRead};
+
+/// How many placements `go` searches ahead when no depth is given.
+const DEFAULT_DEPTH: u8 = 2;
+/// The deepest `go` searches, enough for a whole match with the usual hands.
+const MAX_DEPTH: u8 = 10;
+
+fn parse_position(words: &[&str]) -> Result<GameState, String> {
+  if words.len() != 5 || words[1] != "hands" {
+    return Err("use position <board> hands <blue hand> <red hand> <color>".to_string());
+  }
+  let board = parse_board(words[0]).ok_or("the board is invalid")?;
+  let blue = parse_hand(words[2]).ok_or("blue's hand is invalid")?;
+  let red = parse_hand(words[3]).ok_or("red's hand is invalid")?;
+  let to_move = parse_color(words[4]).ok_or("the color to move is invalid")?;
+  Ok(GameState::new(board, blue, red, to_move))
+}
+
+fn parse_depth(words: &[&str]) -> Result<u8, String> {
+  match words.len() {
+    0 => Ok(DEFAULT_DEPTH),
+    2 if words[0] == "depth" => match words[1].parse() {
+      Ok(depth) if depth >= 1 && depth <= MAX_DEPTH => Ok(depth),
+      _ => Err(format!("the depth must be from 1 to {}", MAX_DEPTH))
+    },
+    _ => Err("use go depth <n>".to_string())
+  }
+}
+
+fn format_best(result: &SearchResult) -> String {
+  match result.best {
+    Some(ref mv) => format!("bestmove {}", format_move(mv)),
+    None => "bestmove none".to_string()
+  }
+}
+
+fn main() {
+  let mut searcher = Searcher::new();
+  let mut position: Option<GameState> = None;
+  let mut last: Option<SearchResult> = None;
+  let input = stdin();
+  for line in input.lock().lines() {
+    let line = match line {
+      Ok(l) => l,
+      Err(_) => break
+    };
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.first() {
+      Some(c) => *c,
+      None => continue
+    };
+    let reply = match command {
+      "position" => parse_position(&words[1..]).map(|state| {
+        position = Some(state);
+        last = None;
+        Vec::new()
+      }),
+      "go" => match (parse_depth(&words[1..]), position.as_ref()) {
+        (Ok(depth), Some(state)) => {
+          let mut lines = Vec::new();
+          let result = searcher.search_iteratively(state, depth, |r| {
+            lines.push(format!("info depth {} value {} nodes {}", r.depth, r.value, r.nodes));
+          });
+          lines.push(format_best(&result));
+          last = Some(result);
+          Ok(lines)
+        },
+        (Err(e), _) => Err(e),
+        (_, None) => Err("set a position first".to_string())
+      },
+      "bestmove" => match last {
+        Some(ref result) => Ok(vec![format_best(result)]),
+        None => Err("nothing has been searched".to_string())
+      },
+      "odds" if words.len() == 3 => {
+        let card = |w: &str| parse_card(w).or_else(|| TetraMaster::parse_card(w));
+        match (card(words[1]), card(words[2])) {
+          (Some(attacker), Some(defender)) => {
+            let odds = TetraMaster::odds(&attacker, &defender);
+            Ok(vec![format!("odds {} {} {}", odds.attacker, odds.defender, odds.draw)])
+          },
+          _ => Err("a card is invalid".to_string())
+        }
+      },
+      "odds" => Err("use odds <attacker> <defender>".to_string()),
+      "isready" => Ok(vec!["readyok".to_string()]),
+      "quit" => break,
+      _ => Err(format!("unknown command {}", command))
+    };
+    match reply {
+      Ok(lines) => for line in lines {
+        println!("{}", line);
+      },
+      Err(reason) => println!("error {}", reason)
+    }
+  }
+}
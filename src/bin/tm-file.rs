@@ -0,0 +1,223 @@
+//! Plays a match by passing a signed file back and forth, with no server.
+//!
+//! Blue opens a game with `new` and sends the file to red, who joins with `join` and sends it back.
+//! From then on each player runs `play` on their turn, which checks the file, reveals what they owe
+//! and makes their move, and sends the file on. `reveal` does the same without moving, for when the
+//! other player moves next or the match is over.
+//!
+//! Each player's secrets are kept next to the game file, in a file named after it and their color.
+//! Keep it, and don't send it.
+
+extern crate rand;
+extern crate tetra_master;
+
+use rand::thread_rng;
+
+use tetra_master::{Board, Card, Color, Space};
+use tetra_master::correspondence::{GameFile, Side, Waiting};
+use tetra_master::generator::BoardGenerator;
+use tetra_master::lobby::rules_allowed;
+use tetra_master::net::Rules;
+use tetra_master::notation::{format_color, format_position, parse_hand, parse_position};
+use tetra_master::state::Move;
+
+use std::env::args;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+fn show_board(board: &Board) {
+  for row in &board.spaces {
+    let spaces: Vec<String> = row.iter()
+      .map(|space| match *space {
+        Space::Card(ref c) => format!("{}{}", &format_color(c.color)[..1], c.card.card.to_string()),
+        _ => format!(" {}", space.to_string())
+      })
+      .collect();
+    println!("|{}|", spaces.join("|"));
+  }
+}
+
+fn show_hand(hand: &[Card]) -> String {
+  hand.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn read(path: &str) -> Result<String, String> {
+  let mut text = String::new();
+  File::open(path)
+    .and_then(|mut f| f.read_to_string(&mut text))
+    .map_err(|e| format!("Could not read {}: {}", path, e))?;
+  Ok(text)
+}
+
+fn write(path: &str, text: &str) -> Result<(), String> {
+  File::create(path)
+    .and_then(|mut f| f.write_all(text.as_bytes()))
+    .map_err(|e| format!("Could not write {}: {}", path, e))
+}
+
+fn side_path(path: &str, color: Color) -> String {
+  format!("{}.{}", path, format_color(color))
+}
+
+fn load_side(path: &str, color: Color) -> Option<Side> {
+  read(&side_path(path, color)).ok().and_then(|t| Side::parse(&t))
+}
+
+/// Opens a game file as the player whose secrets are kept next to it. If both players' secrets
+/// are there, the game is opened as the player who has to act.
+fn load(path: &str) -> Result<(GameFile, Side), String> {
+  let sides: Vec<Side> = [Color::Blue, Color::Red].iter().filter_map(|&c| load_side(path, c)).collect();
+  if sides.is_empty() {
+    return Err(format!("Neither player's secrets are in {} or {}", side_path(path, Color::Blue), side_path(path, Color::Red)));
+  }
+  let text = read(path)?;
+  let file = GameFile::parse(&text, &sides[0].key).map_err(|e| format!("The game file can't be trusted: {}", e))?;
+  let waiting = file.replay()?.waiting.player();
+  let side = match sides.iter().position(|s| Some(s.color) == waiting) {
+    Some(i) => sides[i].clone(),
+    None => sides[0].clone()
+  };
+  if let Some(sent) = side.sent {
+    if !file.continues(sent) {
+      return Err("The game file changed what you sent before".to_string());
+    }
+  }
+  Ok((file, side))
+}
+
+fn save(path: &str, file: &GameFile, side: &mut Side) -> Result<(), String> {
+  write(path, &file.to_text(&side.key)?)?;
+  side.sent = Some(file.digest());
+  write(&side_path(path, side.color), &side.to_text())
+}
+
+fn show(file: &GameFile, side: &Side) -> Result<(), String> {
+  let replay = file.replay()?;
+  show_board(&replay.state.board);
+  println!("blue: {}", show_hand(&replay.state.blue_hand));
+  println!("red: {}", show_hand(&replay.state.red_hand));
+  if let Some(turn) = replay.pending {
+    println!("{} played at {}, and the battles are rolled once {} reveals their seed.",
+      format_color(turn.color),
+      format_position((turn.mv.row, turn.mv.column)),
+      format_color(turn.color.opponent()));
+  }
+  let you = |color: Color| if color == side.color { "you" } else { format_color(color) };
+  match replay.waiting {
+    Waiting::Join => println!("Waiting for red to join."),
+    Waiting::Hand => println!("Waiting for blue ({}) to show their cards.", you(Color::Blue)),
+    Waiting::Seed(color) => println!("Waiting for {} to reveal their seed.", you(color)),
+    Waiting::Move(color) => println!("Waiting for {} to move.", you(color)),
+    Waiting::Over => {
+      let score = replay.state.score(true);
+      println!("The match is over: blue {}, red {}.", score.blue, score.red);
+    }
+  }
+  Ok(())
+}
+
+/// Does what the player owes before they can move: showing their cards and revealing their seed.
+fn catch_up(file: &mut GameFile, side: &Side) -> Result<(), String> {
+  match file.replay()?.waiting {
+    Waiting::Hand if side.color == Color::Blue => file.show_hand(side.hand.clone(), &side.secret),
+    Waiting::Seed(color) if color == side.color => file.reveal(color, &side.secret),
+    _ => Ok(())
+  }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+  let command = args.get(0).map(|c| c.as_str());
+  let path = match args.get(1) {
+    Some(p) => p.as_str(),
+    None => return Err(usage())
+  };
+  match (command, args.len()) {
+    (Some("new"), 4) | (Some("new"), 5) => {
+      if Path::new(path).exists() {
+        return Err(format!("{} already exists", path));
+      }
+      let hand = parse_hand(&args[3]).ok_or("The cards are invalid")?;
+      let (width, height) = match args.get(4) {
+        Some(size) => {
+          let parts: Vec<usize> = size.split('x').filter_map(|n| n.parse().ok()).collect();
+          if parts.len() != 2 {
+            return Err(format!("{} is not a size", size));
+          }
+          (parts[0], parts[1])
+        },
+        None => (4, 4)
+      };
+      let rules = Rules {
+        width: width,
+        height: height,
+        hand_size: hand.len()
+      };
+      // The board can only be generated for a size that is allowed.
+      if !rules_allowed(&rules) {
+        return Err(format!("{} cards each on a {}x{} board isn't allowed", hand.len(), width, height));
+      }
+      let board = BoardGenerator::new().size(width, height).generate();
+      let mut side = Side::new(Color::Blue, &args[2], hand, &mut thread_rng());
+      let file = GameFile::open(rules, board, &side.hand, &side.secret)?;
+      save(path, &file, &mut side)?;
+      println!("Opened {}. Send it to red, and tell them the key.", path);
+      Ok(())
+    },
+    (Some("join"), 4) => {
+      let text = read(path)?;
+      let mut file = GameFile::parse(&text, &args[2]).map_err(|e| format!("The game file can't be trusted: {}", e))?;
+      let hand = parse_hand(&args[3]).ok_or("The cards are invalid")?;
+      let mut side = Side::new(Color::Red, &args[2], hand.clone(), &mut thread_rng());
+      file.join(hand, &side.secret)?;
+      save(path, &file, &mut side)?;
+      println!("Joined {}. Send it back to blue.", path);
+      Ok(())
+    },
+    (Some("show"), 2) => {
+      let (file, side) = load(path)?;
+      show(&file, &side)
+    },
+    (Some("reveal"), 2) => {
+      let (mut file, mut side) = load(path)?;
+      catch_up(&mut file, &side)?;
+      save(path, &file, &mut side)?;
+      show(&file, &side)
+    },
+    (Some("play"), 5) | (Some("play"), 6) => {
+      let (mut file, mut side) = load(path)?;
+      let numbers: Vec<usize> = args[2..5].iter().filter_map(|n| n.parse().ok()).collect();
+      if numbers.len() != 3 {
+        return Err("Give the card's place in your hand, the row and the column".to_string());
+      }
+      let first = match args.get(5) {
+        Some(p) => Some(parse_position(p).ok_or("The card to battle first is invalid")?),
+        None => None
+      };
+      catch_up(&mut file, &side)?;
+      file.play(side.color, Move::new(numbers[0], numbers[1], numbers[2]), first, &side.secret)?;
+      save(path, &file, &mut side)?;
+      show(&file, &side)
+    },
+    _ => Err(usage())
+  }
+}
+
+fn usage() -> String {
+  [
+    "Usage:",
+    "  tm-file new <file> <key> <cards> (width)x(height)   opens a game as blue",
+    "  tm-file join <file> <key> <cards>                   joins a game as red",
+    "  tm-file show <file>                                 shows the match",
+    "  tm-file play <file> <card> <row> <column> (first)   moves, picking which card to battle first if asked",
+    "  tm-file reveal <file>                               reveals what you owe without moving",
+    "Cards are written like 1M23:a5,2P34:0f. Cards in hand count from 0, rows and columns from 1."
+  ].join("\n")
+}
+
+fn main() {
+  let args: Vec<String> = args().skip(1).collect();
+  if let Err(e) = run(&args) {
+    println!("{}", e);
+  }
+}
@@ -0,0 +1,415 @@
+//! Serves the engine over HTTP on this machine only, answering in JSON.
+//!
+//! Every request is a `GET`, with its arguments in the query string. Cards, states and colors are
+//! written as in `notation`, so states need their spaces encoded, like `state=...+blue`.
+//!
+//! - `/card?card=<card>`: whether a card is valid, and its stats. Arrows may be left out.
+//! - `/odds?attacker=<card>&defender=<card>`: the chance of each result of a battle.
+//! - `/battle?attacker=<card>&defender=<card>&seed=<n>`: rolls a battle. The same seed always
+//!   rolls the same way.
+//! - `/evaluate?state=<state>`: how good a match is for the player to move, without looking ahead.
+//! - `/suggest?state=<state>&depth=<n>`: the best move for the player to move, looking 1 to 3
+//!   placements ahead.
+//! - `/game?seed=<n>&size=<w>x<h>&blue=<depth>&red=<depth>`: plays a whole match between two AIs
+//!   looking the given number of placements ahead, with random cards.
+//!
+//! Searches that would look at too many positions look fewer placements ahead than asked.
+//! `suggest` replies with the depth it reached.
+//!
+//! A request that can't be answered gets an error status and `{"error": <reason>}`. While
+//! `MAX_REQUESTS` requests are being answered, any more get `503` straight away.
+
+extern crate rand;
+extern crate tetra_master;
+
+use rand::{thread_rng, Rng};
+
+use tetra_master::{seeded_rng, Board, BattleResult, Card, Color, TetraMaster};
+use tetra_master::ai::{evaluate, Searcher, SearchResult};
+use tetra_master::generator::BoardGenerator;
+use tetra_master::lobby::MAX_SIZE;
+use tetra_master::notation::{format_card, format_color, format_state, parse_card, parse_state};
+use tetra_master::state::GameState;
+
+use std::cmp;
+use std::collections::HashMap;
+use std::env::args;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// The port listened on when none is given.
+const DEFAULT_PORT: u16 = 7879;
+/// How many placements ahead `suggest` looks when no depth is given.
+const DEFAULT_DEPTH: u8 = 2;
+/// The deepest any search may go, so one request can't keep the server busy for long.
+const MAX_DEPTH: u8 = 3;
+/// About how many positions one search may look at. A search that would look at more is made
+/// shallower.
+const NODE_BUDGET: u64 = 20_000;
+/// How many requests are answered at once.
+const MAX_REQUESTS: usize = 4;
+/// How many seconds a client has to send its request.
+const READ_TIMEOUT: u64 = 5;
+
+/// How many requests are being answered.
+static ANSWERING: AtomicUsize = AtomicUsize::new(0);
+
+/// A reply: its HTTP status and its JSON body.
+type Response = (u16, String);
+
+fn json_string(text: &str) -> String {
+  let mut json = String::with_capacity(text.len() + 2);
+  json.push('"');
+  for c in text.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+      c => json.push(c)
+    }
+  }
+  json.push('"');
+  json
+}
+
+fn json_position(pos: (usize, usize)) -> String {
+  format!("[{}, {}]", pos.0, pos.1)
+}
+
+fn json_card(card: &Card) -> String {
+  format!("{{\"card\": {}, \"power\": {}, \"class\": {}, \"physical_defense\": {}, \"magical_defense\": {}, \"arrows\": {}}}",
+    json_string(&format_card(card)),
+    card.power,
+    json_string(&card.class.as_char().to_string()),
+    card.physical_defense,
+    card.magical_defense,
+    card.arrows.flags)
+}
+
+fn json_result(result: BattleResult) -> String {
+  json_string(match result {
+    BattleResult::Attacker => "attacker",
+    BattleResult::Defender => "defender",
+    BattleResult::Draw => "draw"
+  })
+}
+
+fn error(status: u16, reason: &str) -> Response {
+  (status, format!("{{\"error\": {}}}", json_string(reason)))
+}
+
+/// Decodes `%xx` escapes and `+` in part of a query string.
+fn decode(text: &str) -> Option<String> {
+  let bytes = text.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'%' => {
+        let hex = text.get(i + 1..i + 3)?;
+        decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        i += 3;
+      },
+      b'+' => {
+        decoded.push(b' ');
+        i += 1;
+      },
+      b => {
+        decoded.push(b);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8(decoded).ok()
+}
+
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+  let mut args = HashMap::new();
+  for pair in query.split('&').filter(|p| !p.is_empty()) {
+    let mut parts = pair.splitn(2, '=');
+    let key = decode(parts.next().unwrap_or(""))?;
+    let value = decode(parts.next().unwrap_or(""))?;
+    args.insert(key, value);
+  }
+  Some(args)
+}
+
+/// Gets an argument, or says it is missing or invalid.
+fn arg<T, F: Fn(&str) -> Option<T>>(args: &HashMap<String, String>, name: &str, parse: F) -> Result<T, String> {
+  match args.get(name) {
+    Some(value) => parse(value).ok_or_else(|| format!("{} is invalid", name)),
+    None => Err(format!("{} is missing", name))
+  }
+}
+
+/// Like `arg`, but with a value to use when the argument is left out.
+fn arg_or<T, F: Fn(&str) -> Option<T>>(args: &HashMap<String, String>, name: &str, default: T, parse: F) -> Result<T, String> {
+  if args.contains_key(name) {
+    arg(args, name, parse)
+  } else {
+    Ok(default)
+  }
+}
+
+fn any_card(text: &str) -> Option<Card> {
+  parse_card(text).or_else(|| TetraMaster::parse_card(text))
+}
+
+fn depth(text: &str) -> Option<u8> {
+  text.parse().ok().filter(|&d| d >= 1 && d <= MAX_DEPTH)
+}
+
+/// Searches one placement deeper at a time, up to `depth`, stopping early if the next search
+/// looks likely to look at more than `NODE_BUDGET` positions. Each search is guessed to grow by as
+/// much as the one before it did.
+fn search(searcher: &mut Searcher, state: &GameState, depth: u8) -> SearchResult {
+  let mut result = searcher.search(state, 1);
+  let mut before = 1;
+  while result.depth < depth {
+    let growth = result.nodes / cmp::max(before, 1);
+    if result.nodes.saturating_mul(growth) > NODE_BUDGET {
+      break;
+    }
+    before = result.nodes;
+    result = searcher.search(state, result.depth + 1);
+  }
+  result
+}
+
+fn size(text: &str) -> Option<(usize, usize)> {
+  let parts: Vec<usize> = text.split('x').filter_map(|n| n.parse().ok()).collect();
+  if parts.len() == 2 && parts[0] > 0 && parts[1] > 0 && parts[0] <= MAX_SIZE && parts[1] <= MAX_SIZE {
+    Some((parts[0], parts[1]))
+  } else {
+    None
+  }
+}
+
+/// Gets the `state` argument. Its board can be no bigger than a lobby's tables, and its hands no
+/// bigger than the usual hand for the board, so one request can't keep the server busy for long.
+fn state_arg(args: &HashMap<String, String>) -> Result<GameState, String> {
+  let state = arg(args, "state", parse_state)?;
+  let (width, height) = (state.board.width(), state.board.height());
+  let hand_size = Board::hand_size(width, height);
+  if width > MAX_SIZE || height > MAX_SIZE {
+    Err(format!("the board can be at most {}x{}", MAX_SIZE, MAX_SIZE))
+  } else if state.blue_hand.len() > hand_size || state.red_hand.len() > hand_size {
+    Err(format!("hands can have at most {} cards on a {}x{} board", hand_size, width, height))
+  } else {
+    Ok(state)
+  }
+}
+
+fn card(args: &HashMap<String, String>) -> Result<String, String> {
+  arg(args, "card", any_card).map(|c| json_card(&c))
+}
+
+fn odds(args: &HashMap<String, String>) -> Result<String, String> {
+  let attacker = arg(args, "attacker", any_card)?;
+  let defender = arg(args, "defender", any_card)?;
+  let odds = TetraMaster::odds(&attacker, &defender);
+  Ok(format!("{{\"attacker\": {}, \"defender\": {}, \"draw\": {}}}", odds.attacker, odds.defender, odds.draw))
+}
+
+fn battle(args: &HashMap<String, String>) -> Result<String, String> {
+  let attacker = arg(args, "attacker", any_card)?;
+  let defender = arg(args, "defender", any_card)?;
+  let seed = arg_or(args, "seed", thread_rng().gen(), |s| s.parse().ok())?;
+  let roll = TetraMaster::roll_with_rng(&attacker, &defender, &mut seeded_rng(seed));
+  Ok(format!("{{\"seed\": {}, \"attack\": {}, \"defense\": {}, \"result\": {}}}", seed, roll.attack, roll.defense, json_result(roll.result())))
+}
+
+fn evaluate_state(args: &HashMap<String, String>) -> Result<String, String> {
+  let state = state_arg(args)?;
+  let score = state.score(true);
+  Ok(format!("{{\"value\": {}, \"blue\": {}, \"red\": {}, \"over\": {}}}",
+    evaluate(&state, state.to_move),
+    score.get(Color::Blue),
+    score.get(Color::Red),
+    state.is_over()))
+}
+
+fn suggest(args: &HashMap<String, String>) -> Result<String, String> {
+  let state = state_arg(args)?;
+  let depth = arg_or(args, "depth", DEFAULT_DEPTH, depth)?;
+  let result = search(&mut Searcher::new(), &state, depth);
+  let best = match result.best {
+    Some(mv) => format!("{{\"card\": {}, \"row\": {}, \"column\": {}}}", mv.card, mv.row, mv.column),
+    None => "null".to_string()
+  };
+  Ok(format!("{{\"move\": {}, \"value\": {}, \"depth\": {}, \"nodes\": {}}}", best, result.value, result.depth, result.nodes))
+}
+
+fn game(args: &HashMap<String, String>) -> Result<String, String> {
+  let seed = arg_or(args, "seed", thread_rng().gen(), |s| s.parse().ok())?;
+  let (width, height) = arg_or(args, "size", (4, 4), size)?;
+  let depths = [arg_or(args, "blue", DEFAULT_DEPTH, depth)?, arg_or(args, "red", DEFAULT_DEPTH, depth)?];
+  let mut rng = seeded_rng(seed);
+  let hand_size = Board::hand_size(width, height);
+  let board = BoardGenerator::new().size(width, height).generate_with_rng(&mut rng);
+  let blue = (0..hand_size).map(|_| Card::random(&mut rng)).collect();
+  let red = (0..hand_size).map(|_| Card::random(&mut rng)).collect();
+  let to_move = if rng.gen() { Color::Blue } else { Color::Red };
+  let mut state = GameState::new(board, blue, red, to_move);
+  let start = format_state(&state);
+  let mut searchers = [Searcher::new(), Searcher::new()];
+  let mut moves = Vec::new();
+  while !state.is_over() {
+    let color = state.to_move;
+    let i = if color == Color::Blue { 0 } else { 1 };
+    let mv = search(&mut searchers[i], &state, depths[i]).best.expect("Match isn't over");
+    let card = state.hand(color)[mv.card];
+    let report = state.apply_reported(mv, None, &mut rng).expect("Searched moves are legal");
+    let flipped: Vec<String> = report.flipped().into_iter().map(json_position).collect();
+    moves.push(format!("{{\"color\": {}, \"card\": {}, \"row\": {}, \"column\": {}, \"flipped\": [{}]}}",
+      json_string(format_color(color)),
+      json_string(&format_card(&card)),
+      mv.row,
+      mv.column,
+      flipped.join(", ")));
+  }
+  let score = state.score(true);
+  let winner = match score.winner() {
+    Some(color) => json_string(format_color(color)),
+    None => "null".to_string()
+  };
+  Ok(format!("{{\"seed\": {}, \"start\": {}, \"moves\": [{}], \"end\": {}, \"blue\": {}, \"red\": {}, \"winner\": {}}}",
+    seed,
+    json_string(&start),
+    moves.join(", "),
+    json_string(&format_state(&state)),
+    score.get(Color::Blue),
+    score.get(Color::Red),
+    winner))
+}
+
+fn respond(method: &str, target: &str) -> Response {
+  if method != "GET" {
+    return error(405, "only GET is supported");
+  }
+  let mut parts = target.splitn(2, '?');
+  let path = parts.next().unwrap_or("");
+  let args = match parse_query(parts.next().unwrap_or("")) {
+    Some(a) => a,
+    None => return error(400, "the query string is invalid")
+  };
+  let result = match path {
+    "/card" => card(&args),
+    "/odds" => odds(&args),
+    "/battle" => battle(&args),
+    "/evaluate" => evaluate_state(&args),
+    "/suggest" => suggest(&args),
+    "/game" => game(&args),
+    _ => return error(404, "there is nothing here")
+  };
+  match result {
+    Ok(body) => (200, body),
+    Err(reason) => error(400, &reason)
+  }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+  match status {
+    200 => "OK",
+    400 => "Bad Request",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    503 => "Service Unavailable",
+    _ => "Error"
+  }
+}
+
+/// Reads a request, returning its first line.
+fn read_request(stream: &TcpStream) -> Option<String> {
+  let mut reader = BufReader::new(match stream.try_clone() {
+    Ok(s) => s,
+    Err(_) => return None
+  });
+  let mut request = String::new();
+  if reader.read_line(&mut request).is_err() {
+    return None;
+  }
+  // The headers don't change the reply, but are read so the client isn't cut off mid-request.
+  let mut header = String::new();
+  while let Ok(n) = reader.read_line(&mut header) {
+    if n == 0 || header.trim().is_empty() {
+      break;
+    }
+    header.clear();
+  }
+  Some(request)
+}
+
+fn reply(mut stream: TcpStream, (status, body): Response) {
+  let _ = write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status, reason_phrase(status), body.len(), body);
+}
+
+fn handle(stream: TcpStream) {
+  let request = match read_request(&stream) {
+    Some(r) => r,
+    None => return
+  };
+  let words: Vec<&str> = request.split_whitespace().collect();
+  let response = if words.len() == 3 {
+    respond(words[0], words[1])
+  } else {
+    error(400, "the request is invalid")
+  };
+  reply(stream, response);
+}
+
+/// Counts a request as being answered until it is dropped.
+struct Answering;
+
+impl Drop for Answering {
+  fn drop(&mut self) {
+    ANSWERING.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+fn main() {
+  let port = match args().nth(1).map(|p| p.parse()) {
+    Some(Ok(p)) => p,
+    Some(Err(_)) => {
+      println!("Usage: tm-http (port)");
+      println!("Serves the engine over HTTP to this machine, answering in JSON.");
+      return;
+    },
+    None => DEFAULT_PORT
+  };
+  let listener = match TcpListener::bind(("127.0.0.1", port)) {
+    Ok(l) => l,
+    Err(e) => {
+      println!("Could not listen on port {}: {}", port, e);
+      return;
+    }
+  };
+  println!("Listening on http://127.0.0.1:{}", port);
+  for stream in listener.incoming() {
+    match stream {
+      Ok(s) => {
+        let _ = s.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT)));
+        if ANSWERING.fetch_add(1, Ordering::SeqCst) >= MAX_REQUESTS {
+          ANSWERING.fetch_sub(1, Ordering::SeqCst);
+          // The request is read anyway, so the client isn't cut off before it sees the reply.
+          let _ = s.set_read_timeout(Some(Duration::from_millis(100)));
+          if read_request(&s).is_some() {
+            reply(s, error(503, "the server is busy, try again soon"));
+          }
+          continue;
+        }
+        let answering = Answering;
+        thread::spawn(move || {
+          let _answering = answering;
+          handle(s)
+        });
+      },
+      Err(e) => println!("Could not accept a request: {}", e)
+    }
+  }
+}
@@ -0,0 +1,212 @@
+//! Plays matches between bots without showing them, and reports who won.
+//!
+//! Each player is one of:
+//!
+//! - `random`: plays a random legal move.
+//! - `ai` or `ai:<depth>`: the searcher from `ai`, looking the given number of placements ahead.
+//! - Anything else is the command line of an external bot, talking as `bot::Bot` describes.
+//!
+//! A bot that times out or keeps making illegal moves forfeits the match, and the other player
+//! wins it.
+
+extern crate rand;
+extern crate tetra_master;
+
+use rand::{thread_rng, Rng, XorShiftRng};
+
+use tetra_master::{seeded_rng, Card, Color};
+use tetra_master::ai::Searcher;
+use tetra_master::bot::{self, Bot, BotError};
+use tetra_master::generator::BoardGenerator;
+use tetra_master::notation::format_color;
+use tetra_master::state::{GameState, Move};
+
+use std::env::args;
+use std::time::Duration;
+
+/// How many placements the `ai` player looks ahead when no depth is given.
+const DEFAULT_DEPTH: u8 = 2;
+
+enum Player {
+  Random,
+  Ai(Searcher, u8),
+  External(Bot)
+}
+
+impl Player {
+  fn from_arg(arg: &str, timeout: Duration) -> Result<Player, String> {
+    if arg == "random" {
+      return Ok(Player::Random);
+    }
+    if arg == "ai" {
+      return Ok(Player::Ai(Searcher::new(), DEFAULT_DEPTH));
+    }
+    if arg.starts_with("ai:") {
+      return match arg[3..].parse() {
+        Ok(depth) if depth > 0 => Ok(Player::Ai(Searcher::new(), depth)),
+        _ => Err(format!("{} is not a depth, which must be at least 1", &arg[3..]))
+      };
+    }
+    Bot::launch(arg)
+      .map(|b| Player::External(b.timeout(timeout)))
+      .map_err(|e| format!("could not start {}: {}", arg, e))
+  }
+
+  fn start(&mut self, color: Color, state: &GameState) -> Result<(), BotError> {
+    match *self {
+      Player::External(ref mut b) => b.start(color, state),
+      _ => Ok(())
+    }
+  }
+
+  fn choose_move(&mut self, state: &GameState, rng: &mut XorShiftRng) -> Result<Move, BotError> {
+    match *self {
+      Player::Random => {
+        let moves = state.legal_moves();
+        Ok(moves[rng.gen_range(0, moves.len())])
+      },
+      Player::Ai(ref mut searcher, depth) => Ok(searcher.search(state, depth).best.expect("Match isn't over")),
+      Player::External(ref mut b) => b.choose_move(state)
+    }
+  }
+
+  fn choose_first(&mut self, targets: &[(usize, usize)]) -> Result<Option<(usize, usize)>, BotError> {
+    match *self {
+      Player::External(ref mut b) => b.choose_first(targets).map(Some),
+      _ => Ok(None)
+    }
+  }
+}
+
+/// How a match ended.
+enum Outcome {
+  Finished(u8, u8),
+  Forfeit(Color, BotError)
+}
+
+fn play_match(players: &mut [Player; 2], state: GameState, rng: &mut XorShiftRng) -> Outcome {
+  let mut state = state;
+  for (i, &color) in [Color::Blue, Color::Red].iter().enumerate() {
+    if let Err(e) = players[i].start(color, &state) {
+      return Outcome::Forfeit(color, e);
+    }
+  }
+  while !state.is_over() {
+    let color = state.to_move;
+    let i = if color == Color::Blue { 0 } else { 1 };
+    let mv = match players[i].choose_move(&state, rng) {
+      Ok(mv) => mv,
+      Err(e) => return Outcome::Forfeit(color, e)
+    };
+    let targets = state.battle_targets(mv);
+    let first = if targets.len() > 1 {
+      match players[i].choose_first(&targets) {
+        Ok(first) => first,
+        Err(e) => return Outcome::Forfeit(color, e)
+      }
+    } else {
+      None
+    };
+    let card = state.hand(color)[mv.card];
+    let report = state.apply_reported(mv, first, rng).expect("Move was checked");
+    for (j, player) in players.iter_mut().enumerate() {
+      if let Player::External(ref mut b) = *player {
+        if let Err(e) = b.tell(color, mv, card, &report) {
+          return Outcome::Forfeit(if j == 0 { Color::Blue } else { Color::Red }, e);
+        }
+      }
+    }
+  }
+  let score = state.score(true);
+  Outcome::Finished(score.get(Color::Blue), score.get(Color::Red))
+}
+
+fn usage() {
+  println!("Usage: tm-match (options) blue red");
+  println!("Plays matches between two players and reports who won.");
+  println!("Each player is random, ai, ai:depth, or the command line of a bot (quote it if it has spaces).");
+  println!("Options:");
+  println!("  --games n      how many matches to play (default 1)");
+  println!("  --seed n       the seed for boards, hands and battles");
+  println!("  --size wxh     the size of the board (default 4x4)");
+  println!("  --timeout s    how many seconds a bot has to reply (default {})", bot::DEFAULT_TIMEOUT);
+}
+
+fn main() {
+  let mut args: Vec<String> = args().skip(1).collect();
+  let mut games: usize = 1;
+  let mut seed: u64 = thread_rng().gen();
+  let mut size = (4, 4);
+  let mut timeout = bot::DEFAULT_TIMEOUT;
+  let mut names = Vec::new();
+  args.reverse();
+  while let Some(arg) = args.pop() {
+    let ok = match arg.as_str() {
+      "--games" => args.pop().and_then(|g| g.parse().ok()).map(|g| games = g).is_some(),
+      "--seed" => args.pop().and_then(|s| s.parse().ok()).map(|s| seed = s).is_some(),
+      "--timeout" => args.pop().and_then(|t| t.parse().ok()).map(|t| timeout = t).is_some(),
+      "--size" => {
+        let parsed = args.pop().and_then(|s| {
+          let parts: Vec<usize> = s.split('x').filter_map(|n| n.parse().ok()).collect();
+          if parts.len() == 2 && parts[0] > 0 && parts[1] > 0 { Some((parts[0], parts[1])) } else { None }
+        });
+        parsed.map(|s| size = s).is_some()
+      },
+      _ if arg.starts_with("--") => false,
+      _ => {
+        names.push(arg);
+        true
+      }
+    };
+    if !ok {
+      usage();
+      return;
+    }
+  }
+  if names.len() != 2 {
+    usage();
+    return;
+  }
+  let timeout = Duration::from_secs(timeout);
+  let mut players = match (Player::from_arg(&names[0], timeout), Player::from_arg(&names[1], timeout)) {
+    (Ok(blue), Ok(red)) => [blue, red],
+    (Err(e), _) | (_, Err(e)) => {
+      println!("{}", e);
+      return;
+    }
+  };
+  println!("Seed {}", seed);
+  let mut rng = seeded_rng(seed);
+  let hand_size = tetra_master::Board::hand_size(size.0, size.1);
+  let mut wins = [0, 0];
+  let mut draws = 0;
+  for game in 1..games + 1 {
+    let board = BoardGenerator::new().size(size.0, size.1).generate_with_rng(&mut rng);
+    let blue = (0..hand_size).map(|_| Card::random(&mut rng)).collect();
+    let red = (0..hand_size).map(|_| Card::random(&mut rng)).collect();
+    let to_move = if rng.gen() { Color::Blue } else { Color::Red };
+    let state = GameState::new(board, blue, red, to_move);
+    let winner = match play_match(&mut players, state, &mut rng) {
+      Outcome::Finished(blue, red) => {
+        println!("Match {}: blue {}, red {}", game, blue, red);
+        if blue > red {
+          Some(Color::Blue)
+        } else if red > blue {
+          Some(Color::Red)
+        } else {
+          None
+        }
+      },
+      Outcome::Forfeit(color, e) => {
+        println!("Match {}: {} forfeits because {}", game, format_color(color), e);
+        Some(color.opponent())
+      }
+    };
+    match winner {
+      Some(Color::Blue) => wins[0] += 1,
+      Some(Color::Red) => wins[1] += 1,
+      None => draws += 1
+    }
+  }
+  println!("Blue ({}) won {}, red ({}) won {}, {} drawn", names[0], wins[0], names[1], wins[1], draws);
+}
@@ -0,0 +1,98 @@
+extern crate rand;
+extern crate tetra_master;
+
+use rand::{thread_rng, Rng};
+
+use tetra_master::seeded_rng;
+use tetra_master::lobby::{Lobby, PlayerId};
+use tetra_master::net::{self, Connection, Message};
+
+use std::collections::BTreeMap;
+use std::env::args;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// How long to wait between checking for messages when nothing is happening.
+const IDLE_WAIT: u64 = 10;
+
+fn main() {
+  let args: Vec<String> = args().skip(1).collect();
+  let port = match args.get(0).map(|p| p.parse()) {
+    Some(Ok(p)) => p,
+    Some(Err(_)) => {
+      println!("Usage: tm-server (port) (seed)");
+      println!("Runs a lobby where players open tables and play each other.");
+      return;
+    },
+    None => net::DEFAULT_PORT
+  };
+  // A seed makes every board and roll the same from run to run, for scripted clients.
+  let seed = args.get(1).and_then(|s| s.parse().ok()).unwrap_or_else(|| thread_rng().gen());
+  let incoming = match net::accept_all(("0.0.0.0", port)) {
+    Ok(rx) => rx,
+    Err(e) => {
+      println!("Could not listen on port {}: {}", port, e);
+      return;
+    }
+  };
+  println!("Listening on port {}", port);
+  let mut lobby = Lobby::new(seeded_rng(seed));
+  let mut players: BTreeMap<PlayerId, Connection> = BTreeMap::new();
+  let mut next_player: PlayerId = 1;
+  loop {
+    let mut busy = false;
+    while let Ok(accepted) = incoming.try_recv() {
+      busy = true;
+      match accepted {
+        Ok(connection) => {
+          println!("Player {} connected from {}", next_player, connection.peer_addr().map(|a| a.to_string()).unwrap_or_default());
+          players.insert(next_player, connection);
+          next_player += 1;
+        },
+        Err(e) => println!("Could not accept a player: {}", e)
+      }
+    }
+    let now = Instant::now();
+    let mut outgoing = lobby.expire(now);
+    let mut gone = Vec::new();
+    for (&player, connection) in &players {
+      while let Some(message) = connection.try_recv() {
+        busy = true;
+        if message == Message::Bye {
+          gone.push((player, connection.lost()));
+          break;
+        }
+        outgoing.extend(lobby.handle(player, message));
+      }
+    }
+    for (player, lost) in gone {
+      players.remove(&player);
+      if lost {
+        println!("Player {} lost their connection", player);
+        outgoing.extend(lobby.disconnect(player, now));
+      } else {
+        println!("Player {} left", player);
+        outgoing.extend(lobby.leave(player));
+      }
+    }
+    for (player, message) in outgoing {
+      let failed = match players.get_mut(&player) {
+        Some(connection) => connection.send(&message).is_err(),
+        None => false
+      };
+      if failed {
+        println!("Player {} can't be reached", player);
+        players.remove(&player);
+        // Anyone this tells is only being told the player is gone, so it can't fail in turn.
+        for (other, message) in lobby.disconnect(player, now) {
+          if let Some(connection) = players.get_mut(&other) {
+            let _ = connection.send(&message);
+          }
+        }
+      }
+    }
+    if !busy {
+      sleep(Duration::from_millis(IDLE_WAIT));
+    }
+  }
+}
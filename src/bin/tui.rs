@@ -0,0 +1,265 @@
+//! Plays a match in the terminal, for when the game's window can't be opened, like over SSH.
+//!
+//! The board is drawn with each card's arrows around its stats, in the color of the player who
+//! owns it, and empty spaces show their row and column. Moves are typed as the card's place in the
+//! hand, the row and the column, like `0 2 3`. After each placement every battle is listed with
+//! both sides' numbers and the cards it flipped.
+
+extern crate rand;
+extern crate tetra_master;
+
+use rand::{thread_rng, Rng, XorShiftRng};
+
+use tetra_master::{seeded_rng, BattleResult, Board, Card, Color, Space};
+use tetra_master::ai::Searcher;
+use tetra_master::generator::BoardGenerator;
+use tetra_master::notation::{format_color, format_position, parse_move, parse_position};
+use tetra_master::report::PlacementReport;
+use tetra_master::state::{GameState, Move};
+
+use std::env::args;
+use std::io::{stdin, stdout, BufRead, Write};
+
+/// How many placements the computer looks ahead when no depth is given.
+const DEFAULT_DEPTH: u8 = 2;
+
+/// Who plays red.
+enum Opponent {
+  Computer(Searcher, u8),
+  /// Plays a random legal move, like the computer in the game's window.
+  Random,
+  /// Another player at the same terminal.
+  Human
+}
+
+fn paint(text: &str, color: Color, colored: bool) -> String {
+  if !colored {
+    return text.to_string();
+  }
+  let code = match color {
+    Color::Blue => "34",
+    Color::Red => "31"
+  };
+  format!("\x1b[1;{}m{}\x1b[0m", code, text)
+}
+
+/// Draws a card as three lines of eight characters, with its arrows around its stats.
+fn card_lines(card: &Card) -> [String; 3] {
+  let a = card.arrows;
+  let mark = |on: bool, c: char| if on { c } else { ' ' };
+  [
+    format!("{}  {}   {}", mark(a.northwest(), '\\'), mark(a.north(), '^'), mark(a.northeast(), '/')),
+    format!("{} {} {}", mark(a.west(), '<'), card.to_string(), mark(a.east(), '>')),
+    format!("{}  {}   {}", mark(a.southwest(), '/'), mark(a.south(), 'v'), mark(a.southeast(), '\\'))
+  ]
+}
+
+fn space_lines(space: &Space, row: usize, column: usize, colored: bool) -> [String; 3] {
+  match *space {
+    Space::Card(ref c) => {
+      let lines = card_lines(&c.card.card);
+      [paint(&lines[0], c.color, colored), paint(&lines[1], c.color, colored), paint(&lines[2], c.color, colored)]
+    },
+    Space::Block => ["#".repeat(8), "#".repeat(8), "#".repeat(8)],
+    Space::Empty => [" ".repeat(8), format!("{:^8}", format_position((row, column))), " ".repeat(8)]
+  }
+}
+
+fn show_board(board: &Board, colored: bool) {
+  let columns: Vec<String> = (1..board.width() + 1).map(|c| format!("{:^8}", c)).collect();
+  println!("    {}", columns.join(" "));
+  let border = format!("   +{}", "--------+".repeat(board.width()));
+  println!("{}", border);
+  for row in 1..board.height() + 1 {
+    let spaces: Vec<[String; 3]> = (1..board.width() + 1)
+      .map(|column| space_lines(board.space(row, column), row, column, colored))
+      .collect();
+    for line in 0..3 {
+      let label = if line == 1 { format!("{:>2}", row) } else { "  ".to_string() };
+      let parts: Vec<&str> = spaces.iter().map(|s| s[line].as_str()).collect();
+      println!("{} |{}|", label, parts.join("|"));
+    }
+    println!("{}", border);
+  }
+}
+
+/// Draws a hand side by side, with each card's place in the hand below it.
+fn show_hand(hand: &[Card], color: Color, colored: bool) {
+  println!("{}:", format_color(color));
+  let cards: Vec<[String; 3]> = hand.iter().map(card_lines).collect();
+  for line in 0..3 {
+    let parts: Vec<String> = cards.iter().map(|c| paint(&c[line], color, colored)).collect();
+    println!("  {}", parts.join("  "));
+  }
+  let places: Vec<String> = (0..hand.len()).map(|i| format!("{:^8}", i)).collect();
+  println!("  {}", places.join("  "));
+}
+
+fn show_report(report: &PlacementReport) {
+  for battle in &report.battles {
+    let result = match battle.result {
+      BattleResult::Attacker => "the attacker wins",
+      BattleResult::Defender => "the defender wins",
+      BattleResult::Draw => "a draw"
+    };
+    println!("{} battles {}: {} to {}, {}.",
+      format_position(battle.attacker),
+      format_position(battle.defender),
+      battle.attack,
+      battle.defense,
+      result);
+    if !battle.flipped.is_empty() {
+      let flipped: Vec<String> = battle.flipped.iter().cloned().map(format_position).collect();
+      println!("  Flipped {}.", flipped.join(" "));
+    }
+  }
+  if !report.taken.is_empty() {
+    let taken: Vec<String> = report.taken.iter().cloned().map(format_position).collect();
+    println!("Took {} without a battle.", taken.join(" "));
+  }
+}
+
+/// Reads a line, returning `None` once the input ends or the player quits.
+fn prompt(question: &str) -> Option<String> {
+  print!("{}", question);
+  let _ = stdout().flush();
+  let mut line = String::new();
+  match stdin().lock().read_line(&mut line) {
+    Ok(0) | Err(_) => None,
+    Ok(_) => match line.trim() {
+      "q" | "quit" => None,
+      text => Some(text.to_string())
+    }
+  }
+}
+
+/// Asks the player to move for the color to move, and which card to battle first if they can pick.
+fn ask_move(state: &GameState) -> Option<(Move, Option<(usize, usize)>)> {
+  let question = format!("{} to move (card row column): ", format_color(state.to_move));
+  let mv = loop {
+    match parse_move(&prompt(&question)?) {
+      Some(mv) if state.is_legal(mv) => break mv,
+      Some(_) => println!("That card can't be played there."),
+      None => println!("Type the card's place in your hand, the row and the column, like 0 2 3.")
+    }
+  };
+  let targets = state.battle_targets(mv);
+  if targets.len() < 2 {
+    return Some((mv, None));
+  }
+  let choices: Vec<String> = targets.iter().cloned().map(format_position).collect();
+  let question = format!("Battle which card first? ({}): ", choices.join(" "));
+  loop {
+    match parse_position(&prompt(&question)?) {
+      Some(first) if targets.contains(&first) => return Some((mv, Some(first))),
+      _ => println!("Pick one of {}.", choices.join(" "))
+    }
+  }
+}
+
+fn usage() {
+  println!("Usage: tui (options)");
+  println!("Plays a match in the terminal. Type q to stop.");
+  println!("Options:");
+  println!("  --depth n      how many placements the computer looks ahead (default {})", DEFAULT_DEPTH);
+  println!("  --random       play against a computer that moves at random");
+  println!("  --hot-seat     play against someone at the same terminal");
+  println!("  --seed n       the seed for the board, hands and battles");
+  println!("  --size wxh     the size of the board (default 4x4)");
+  println!("  --no-color     draw the board without colors");
+}
+
+fn main() {
+  let mut args: Vec<String> = args().skip(1).collect();
+  let mut opponent = Opponent::Computer(Searcher::new(), DEFAULT_DEPTH);
+  let mut seed: u64 = thread_rng().gen();
+  let mut size = (4, 4);
+  let mut colored = true;
+  args.reverse();
+  while let Some(arg) = args.pop() {
+    let ok = match arg.as_str() {
+      "--depth" => args.pop().and_then(|d| d.parse().ok()).filter(|&d| d > 0).map(|d| opponent = Opponent::Computer(Searcher::new(), d)).is_some(),
+      "--random" => {
+        opponent = Opponent::Random;
+        true
+      },
+      "--hot-seat" => {
+        opponent = Opponent::Human;
+        true
+      },
+      "--seed" => args.pop().and_then(|s| s.parse().ok()).map(|s| seed = s).is_some(),
+      "--size" => {
+        let parsed = args.pop().and_then(|s| {
+          let parts: Vec<usize> = s.split('x').filter_map(|n| n.parse().ok()).collect();
+          if parts.len() == 2 && parts[0] > 0 && parts[1] > 0 { Some((parts[0], parts[1])) } else { None }
+        });
+        parsed.map(|s| size = s).is_some()
+      },
+      "--no-color" => {
+        colored = false;
+        true
+      },
+      _ => false
+    };
+    if !ok {
+      usage();
+      return;
+    }
+  }
+
+  println!("Seed {}", seed);
+  let mut rng: XorShiftRng = seeded_rng(seed);
+  let board = BoardGenerator::new().size(size.0, size.1).generate_with_rng(&mut rng);
+  let hand_size = Board::hand_size(size.0, size.1);
+  let blue = (0..hand_size).map(|_| Card::random(&mut rng)).collect();
+  let red = (0..hand_size).map(|_| Card::random(&mut rng)).collect();
+  let to_move = if rng.gen() { Color::Blue } else { Color::Red };
+  let mut state = GameState::new(board, blue, red, to_move);
+  let hot_seat = match opponent {
+    Opponent::Human => true,
+    _ => false
+  };
+
+  while !state.is_over() {
+    println!();
+    show_board(&state.board, colored);
+    let color = state.to_move;
+    let human = color == Color::Blue || hot_seat;
+    // The computer's cards stay hidden, like in the game's window.
+    let shown = if human { color } else { Color::Blue };
+    if !state.hand(shown).is_empty() {
+      show_hand(state.hand(shown), shown, colored);
+    }
+    let (mv, first) = if human {
+      match ask_move(&state) {
+        Some(m) => m,
+        None => return
+      }
+    } else {
+      let mv = match opponent {
+        Opponent::Computer(ref mut searcher, depth) => searcher.search(&state, depth).best.expect("Match isn't over"),
+        _ => {
+          let moves = state.legal_moves();
+          moves[rng.gen_range(0, moves.len())]
+        }
+      };
+      (mv, None)
+    };
+    let card = state.hand(color)[mv.card];
+    let report = state.apply_reported(mv, first, &mut rng).expect("Move was checked");
+    println!("{} played {} at {}.", format_color(color), card.to_string(), format_position((mv.row, mv.column)));
+    show_report(&report);
+  }
+
+  println!();
+  show_board(&state.board, colored);
+  let score = state.score(true);
+  let result = if score.blue > score.red {
+    "blue wins"
+  } else if score.red > score.blue {
+    "red wins"
+  } else {
+    "it's a draw"
+  };
+  println!("The match is over: blue {}, red {}, {}.", score.blue, score.red, result);
+}
@@ -0,0 +1,83 @@
+extern crate tetra_master;
+
+use tetra_master::{Board, Card, Space};
+use tetra_master::net::{self, Connection, Message, PROTOCOL_VERSION};
+use tetra_master::notation::{format_color, format_position};
+
+use std::env::args;
+
+fn show_board(board: &Board) {
+  for row in &board.spaces {
+    let spaces: Vec<String> = row.iter()
+      .map(|space| match *space {
+        Space::Card(ref c) => format!("{}{}", &format_color(c.color)[..1], c.card.card.to_string()),
+        _ => format!(" {}", space.to_string())
+      })
+      .collect();
+    println!("|{}|", spaces.join("|"));
+  }
+}
+
+fn show_hand(hand: &[Card]) -> String {
+  hand.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn main() {
+  let addr = match args().nth(1) {
+    Some(ref a) if a.contains(':') => a.clone(),
+    Some(a) => format!("{}:{}", a, net::DEFAULT_PORT),
+    None => {
+      println!("Usage: watch <address>");
+      println!("Watches a match hosted with game --host.");
+      return;
+    }
+  };
+  let mut connection = match Connection::connect(&addr[..]) {
+    Ok(c) => c,
+    Err(e) => {
+      println!("Could not connect to {}: {}", addr, e);
+      return;
+    }
+  };
+  if let Err(e) = connection.send(&Message::Watch(PROTOCOL_VERSION)) {
+    println!("Could not ask to watch: {}", e);
+    return;
+  }
+  loop {
+    match connection.recv() {
+      Message::Hello(version, _) if version != PROTOCOL_VERSION => {
+        println!("The host's game can't be watched here.");
+        return;
+      },
+      Message::Hello(_, rules) => println!("Waiting for a match on a {}x{} board.", rules.width, rules.height),
+      Message::Placed(color, mv, card) => println!("{} played {} at {}.", format_color(color), card.to_string(), format_position((mv.row, mv.column))),
+      Message::Battle(b) => println!("{} attacked {}: {} to {}, {:?} wins.",
+        format_position(b.attacker),
+        format_position(b.defender),
+        b.attack,
+        b.defense,
+        b.result),
+      Message::Taken(taken) => {
+        let taken: Vec<String> = taken.into_iter().map(format_position).collect();
+        println!("Took {} without a battle.", taken.join(" "));
+      },
+      Message::State(state) => {
+        show_board(&state.board);
+        println!("blue: {}", show_hand(&state.blue_hand));
+        println!("red: {}", show_hand(&state.red_hand));
+        if state.is_over() {
+          let score = state.score(true);
+          println!("The match is over: blue {}, red {}.", score.blue, score.red);
+        } else {
+          println!("{} to move.", format_color(state.to_move));
+        }
+      },
+      Message::Illegal(reason) => println!("The host refused: {}", reason),
+      Message::Bye => {
+        println!("The host left.");
+        return;
+      },
+      _ => {}
+    }
+  }
+}
@@ -0,0 +1,291 @@
+use {Board, Card, Color, OwnedCard, Space, TetraMaster, BattleResult};
+
+use rand::Rng;
+
+/// The width and height of boards that fit in a `BitBoard`.
+pub const SIZE: usize = 4;
+
+/// The neighbor of each cell in each direction, as a mask, in `Direction` order.
+///
+/// Cells are numbered row by row, starting at 0 in the top left. Neighbors off the board are 0.
+const NEIGHBORS: [[u16; 8]; 16] = [
+  [0x0000, 0x0002, 0x0000, 0x0000, 0x0000, 0x0010, 0x0000, 0x0020],
+  [0x0001, 0x0004, 0x0000, 0x0000, 0x0000, 0x0020, 0x0010, 0x0040],
+  [0x0002, 0x0008, 0x0000, 0x0000, 0x0000, 0x0040, 0x0020, 0x0080],
+  [0x0004, 0x0000, 0x0000, 0x0000, 0x0000, 0x0080, 0x0040, 0x0000],
+  [0x0000, 0x0020, 0x0001, 0x0000, 0x0002, 0x0100, 0x0000, 0x0200],
+  [0x0010, 0x0040, 0x0002, 0x0001, 0x0004, 0x0200, 0x0100, 0x0400],
+  [0x0020, 0x0080, 0x0004, 0x0002, 0x0008, 0x0400, 0x0200, 0x0800],
+  [0x0040, 0x0000, 0x0008, 0x0004, 0x0000, 0x0800, 0x0400, 0x0000],
+  [0x0000, 0x0200, 0x0010, 0x0000, 0x0020, 0x1000, 0x0000, 0x2000],
+  [0x0100, 0x0400, 0x0020, 0x0010, 0x0040, 0x2000, 0x1000, 0x4000],
+  [0x0200, 0x0800, 0x0040, 0x0020, 0x0080, 0x4000, 0x2000, 0x8000],
+  [0x0400, 0x0000, 0x0080, 0x0040, 0x0000, 0x8000, 0x4000, 0x0000],
+  [0x0000, 0x2000, 0x0100, 0x0000, 0x0200, 0x0000, 0x0000, 0x0000],
+  [0x1000, 0x4000, 0x0200, 0x0100, 0x0400, 0x0000, 0x0000, 0x0000],
+  [0x2000, 0x8000, 0x0400, 0x0200, 0x0800, 0x0000, 0x0000, 0x0000],
+  [0x4000, 0x0000, 0x0800, 0x0400, 0x0000, 0x0000, 0x0000, 0x0000]
+];
+
+/// The `Arrows` flag for each direction, in `Direction` order.
+const ARROW_FLAGS: [u8; 8] = [1 << 1, 1 << 5, 1 << 7, 1, 1 << 6, 1 << 3, 1 << 2, 1 << 4];
+
+/// The opposite of each direction, in `Direction` order.
+const OPPOSITE: [usize; 8] = [1, 0, 5, 7, 6, 2, 4, 3];
+
+fn bit(cell: usize) -> u16 {
+  1 << cell
+}
+
+/// A compact copy of a 4x4 board for fast searching.
+///
+/// Which cells hold cards, blocks and red cards are kept as masks, with bit `n` standing for cell
+/// `n`. Placing cards and running battles gives the same results as `Board`, given the same rolls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitBoard {
+  pub occupied: u16,
+  pub blocks: u16,
+  /// Cards owned by red. Any other cards are owned by blue.
+  pub red: u16,
+  /// Cards brought to the match by red.
+  pub original_red: u16,
+  /// Cards with an arrow pointing in each direction, in `Direction` order.
+  arrows: [u16; 8],
+  pub cards: [Option<Card>; 16]
+}
+
+impl Default for BitBoard {
+  fn default() -> Self {
+    BitBoard {
+      occupied: 0,
+      blocks: 0,
+      red: 0,
+      original_red: 0,
+      arrows: [0; 8],
+      cards: [None; 16]
+    }
+  }
+}
+
+impl BitBoard {
+  /// Gets the cell for a location on a `Board`, starting at 1.
+  pub fn cell(row: usize, column: usize) -> usize {
+    (row - 1) * SIZE + (column - 1)
+  }
+
+  /// Gets the location on a `Board` of a cell.
+  pub fn position(cell: usize) -> (usize, usize) {
+    (cell / SIZE + 1, cell % SIZE + 1)
+  }
+
+  /// Copies a board, if it is 4x4.
+  pub fn from_board(board: &Board) -> Option<BitBoard> {
+    if board.width() != SIZE || board.height() != SIZE {
+      return None;
+    }
+    let mut bits = BitBoard::default();
+    for (r, row) in board.spaces.iter().enumerate() {
+      for (c, space) in row.iter().enumerate() {
+        let cell = r * SIZE + c;
+        match *space {
+          Space::Block => bits.blocks |= bit(cell),
+          Space::Card(ref card) => {
+            bits.place(cell, card.card.card, card.original_color);
+            bits.set_owner(bit(cell), card.color);
+          },
+          Space::Empty => {}
+        }
+      }
+    }
+    Some(bits)
+  }
+
+  pub fn to_board(&self) -> Board {
+    let mut board = Board::new(SIZE, SIZE);
+    for cell in 0..SIZE * SIZE {
+      let (row, column) = BitBoard::position(cell);
+      if self.blocks & bit(cell) != 0 {
+        *board.space_mut(row, column) = Space::Block;
+      } else if let Some(card) = self.cards[cell] {
+        let original = if self.original_red & bit(cell) != 0 { Color::Red } else { Color::Blue };
+        let mut owned = OwnedCard::new(card, original);
+        owned.color = self.color(cell).unwrap();
+        board.add_card(row, column, owned);
+      }
+    }
+    board
+  }
+
+  /// Cells with nothing in them.
+  pub fn empty(&self) -> u16 {
+    !(self.occupied | self.blocks)
+  }
+
+  /// Cards owned by the given color.
+  pub fn owned_by(&self, color: Color) -> u16 {
+    match color {
+      Color::Red => self.red,
+      Color::Blue => self.occupied & !self.red
+    }
+  }
+
+  pub fn color(&self, cell: usize) -> Option<Color> {
+    if self.occupied & bit(cell) == 0 {
+      None
+    } else if self.red & bit(cell) != 0 {
+      Some(Color::Red)
+    } else {
+      Some(Color::Blue)
+    }
+  }
+
+  /// Counts the cards owned by the given color.
+  pub fn count(&self, color: Color) -> u32 {
+    self.owned_by(color).count_ones()
+  }
+
+  fn set_owner(&mut self, mask: u16, color: Color) {
+    match color {
+      Color::Red => self.red |= mask,
+      Color::Blue => self.red &= !mask
+    }
+  }
+
+  /// Puts a card in an empty cell without running any battles.
+  pub fn place(&mut self, cell: usize, card: Card, color: Color) {
+    let mask = bit(cell);
+    self.occupied |= mask;
+    self.set_owner(mask, color);
+    if color == Color::Red {
+      self.original_red |= mask;
+    } else {
+      self.original_red &= !mask;
+    }
+    for (arrows, &flag) in self.arrows.iter_mut().zip(ARROW_FLAGS.iter()) {
+      if card.arrows.flags & flag != 0 {
+        *arrows |= mask;
+      } else {
+        *arrows &= !mask;
+      }
+    }
+    self.cards[cell] = Some(card);
+  }
+
+  /// All cells next to the given cell.
+  pub fn neighbors(cell: usize) -> u16 {
+    NEIGHBORS[cell].iter().fold(0, |mask, n| mask | n)
+  }
+
+  /// Cells the card in the given cell points an arrow at.
+  pub fn pointed_at(&self, cell: usize) -> u16 {
+    let mut mask = 0;
+    for (&arrows, &neighbor) in self.arrows.iter().zip(NEIGHBORS[cell].iter()) {
+      if arrows & bit(cell) != 0 {
+        mask |= neighbor;
+      }
+    }
+    mask
+  }
+
+  /// Finds the opposing cards the card in the given cell would battle, in the order they are
+  /// battled, and the mask of opposing cards it would take without a battle.
+  pub fn relations(&self, cell: usize) -> (Vec<usize>, u16) {
+    let color = match self.color(cell) {
+      Some(c) => c,
+      None => return (Vec::new(), 0)
+    };
+    let enemies = self.owned_by(color.opponent());
+    let mut battles = Vec::new();
+    let mut takes = 0;
+    for (d, (&arrows, &neighbor)) in self.arrows.iter().zip(NEIGHBORS[cell].iter()).enumerate() {
+      if arrows & bit(cell) == 0 {
+        continue;
+      }
+      let neighbor = neighbor & enemies;
+      if neighbor == 0 {
+        continue;
+      }
+      if self.arrows[OPPOSITE[d]] & neighbor != 0 {
+        battles.push(neighbor.trailing_zeros() as usize);
+      } else {
+        takes |= neighbor;
+      }
+    }
+    (battles, takes)
+  }
+
+  fn do_combo(&mut self, winner: Color, loser: usize) {
+    let combos = self.pointed_at(loser) & self.occupied & !self.owned_by(winner);
+    self.set_owner(combos, winner);
+  }
+
+  /// Runs the battles for the card in the given cell, like `Board::run_battles_pos_with_rng`.
+  pub fn run_battles<R: Rng>(&mut self, cell: usize, rng: &mut R) {
+    let color = match self.color(cell) {
+      Some(c) => c,
+      None => return
+    };
+    let (battles, takes) = self.relations(cell);
+    let mut lost_any = false;
+    for &defender in &battles {
+      let result = TetraMaster::battle_with_rng(self.cards[cell].as_ref().unwrap(), self.cards[defender].as_ref().unwrap(), rng);
+      match result {
+        BattleResult::Attacker => {
+          self.set_owner(bit(defender), color);
+          self.do_combo(color, defender);
+        },
+        BattleResult::Defender => {
+          let defender_color = self.color(defender).unwrap();
+          self.set_owner(bit(cell), defender_color);
+          self.do_combo(defender_color, cell);
+          lost_any = true;
+          break;
+        },
+        BattleResult::Draw => {
+          self.run_battles(cell, rng);
+          return;
+        }
+      }
+    }
+    if !lost_any {
+      self.set_owner(takes, color);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use {seeded_rng, Color, OwnedCard};
+  use generator::BoardGenerator;
+  use rand::Rng;
+
+  #[test]
+  fn plays_the_same_as_board() {
+    for seed in 0..200 {
+      let mut rng = seeded_rng(seed);
+      let mut board = BoardGenerator::new().seed(seed).generate();
+      let mut bits = BitBoard::from_board(&board).unwrap();
+      loop {
+        let empty: Vec<(usize, usize)> = (1..SIZE + 1)
+          .flat_map(|r| (1..SIZE + 1).map(move |c| (r, c)))
+          .filter(|&(r, c)| board.space(r, c).is_empty())
+          .collect();
+        if empty.is_empty() {
+          break;
+        }
+        let (row, column) = empty[rng.gen_range(0, empty.len())];
+        let card = Card::random(&mut rng);
+        let color = if rng.gen() { Color::Red } else { Color::Blue };
+        let cell = BitBoard::cell(row, column);
+        board.add_card(row, column, OwnedCard::new(card, color));
+        bits.place(cell, card, color);
+
+        let rolls = rng.gen();
+        board.run_battles_pos_with_rng(row, column, &mut seeded_rng(rolls));
+        bits.run_battles(cell, &mut seeded_rng(rolls));
+        assert_eq!(format!("{:?}", bits.to_board()), format!("{:?}", board), "seed {}, placed at {:?}", seed, (row, column));
+      }
+    }
+  }
+}
@@ -0,0 +1,268 @@
+use {Card, Color};
+use net::Message;
+use report::PlacementReport;
+use state::{GameState, Move};
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How many seconds a bot has to reply, unless told otherwise.
+pub const DEFAULT_TIMEOUT: u64 = 5;
+/// How many bad replies in a row a bot may give before it forfeits.
+pub const MAX_ILLEGAL: usize = 3;
+/// How many milliseconds a bot has to exit once its input is closed, before it is killed.
+pub const EXIT_GRACE: u64 = 500;
+
+/// Why a bot can't carry on with a match. A bot that fails forfeits.
+#[derive(Debug)]
+pub enum BotError {
+  /// The bot didn't reply in time.
+  Timeout,
+  /// The bot kept replying with something it isn't allowed to, most recently this.
+  Illegal(String),
+  /// The bot closed its output or exited.
+  Closed,
+  Io(io::Error)
+}
+
+impl fmt::Display for BotError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      BotError::Timeout => write!(f, "it took too long to reply"),
+      BotError::Illegal(ref reason) => write!(f, "it made {} bad replies, the last because {}", MAX_ILLEGAL, reason),
+      BotError::Closed => write!(f, "it stopped"),
+      BotError::Io(ref e) => write!(f, "it can't be talked to ({})", e)
+    }
+  }
+}
+
+impl From<io::Error> for BotError {
+  fn from(e: io::Error) -> Self {
+    BotError::Io(e)
+  }
+}
+
+/// An opponent played by another program, talking over its standard input and output.
+///
+/// Each line is one of the messages of `net::Message`:
+///
+/// - `start <color> <state>` is sent when a match starts, with the color the bot plays.
+/// - `state <state>` is sent when it is the bot's turn. The bot replies `move <card> <row>
+///   <column>`, where `card` is the index of the card in its hand, starting at 0, and rows and
+///   columns start at 1.
+/// - `choose <row,column>...` is sent when the card the bot played can battle more than one card.
+///   The bot replies `first <row,column>` with the card to battle first.
+/// - `illegal <reason>` is sent when the bot's last reply wasn't allowed. The bot must reply
+///   again.
+/// - `placed`, `battle` and `taken` are sent after every placement by either player, saying what
+///   happened. The bot doesn't reply to these.
+/// - `bye` is sent when the bot is no longer needed, and its input is then closed. A bot still
+///   running `EXIT_GRACE` milliseconds later is killed.
+///
+/// Anything the bot writes that isn't a message counts as a bad reply. A bot that takes longer than
+/// its timeout to reply, or gives `MAX_ILLEGAL` bad replies in a row, forfeits the match.
+pub struct Bot {
+  child: Child,
+  /// The bot's input, until it is closed.
+  stdin: Option<ChildStdin>,
+  /// Each line the bot writes, if it is a message.
+  incoming: Receiver<Option<Message>>,
+  timeout: Duration
+}
+
+impl Bot {
+  /// Starts a bot from a command line, split on whitespace into the program and its arguments.
+  pub fn launch(command: &str) -> io::Result<Bot> {
+    let mut words = command.split_whitespace();
+    let program = match words.next() {
+      Some(p) => p,
+      None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no command was given"))
+    };
+    let mut child = Command::new(program)
+      .args(words)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .spawn()?;
+    let stdin = child.stdin.take().expect("Input was piped");
+    let reader = BufReader::new(child.stdout.take().expect("Output was piped"));
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+      for line in reader.lines() {
+        let line = match line {
+          Ok(l) => l,
+          Err(_) => break
+        };
+        if line.trim().is_empty() {
+          continue;
+        }
+        if tx.send(Message::parse(&line)).is_err() {
+          return;
+        }
+      }
+    });
+    Ok(Bot {
+      child: child,
+      stdin: Some(stdin),
+      incoming: rx,
+      timeout: Duration::from_secs(DEFAULT_TIMEOUT)
+    })
+  }
+
+  /// Sets how long the bot has to reply.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  pub fn send(&mut self, message: &Message) -> Result<(), BotError> {
+    let stdin = match self.stdin {
+      Some(ref mut s) => s,
+      None => return Err(BotError::Closed)
+    };
+    writeln!(stdin, "{}", message.to_string())?;
+    stdin.flush()?;
+    Ok(())
+  }
+
+  fn recv(&self) -> Result<Option<Message>, BotError> {
+    match self.incoming.recv_timeout(self.timeout) {
+      Ok(m) => Ok(m),
+      Err(RecvTimeoutError::Timeout) => Err(BotError::Timeout),
+      Err(RecvTimeoutError::Disconnected) => Err(BotError::Closed)
+    }
+  }
+
+  /// Waits for a reply the given function accepts, telling the bot why any others weren't.
+  fn ask<T, F: Fn(Message) -> Result<T, String>>(&mut self, accept: F) -> Result<T, BotError> {
+    let mut reason = String::new();
+    for _ in 0..MAX_ILLEGAL {
+      reason = match self.recv()? {
+        Some(message) => match accept(message) {
+          Ok(answer) => return Ok(answer),
+          Err(reason) => reason
+        },
+        None => "that is not a message".to_string()
+      };
+      self.send(&Message::Illegal(reason.clone()))?;
+    }
+    Err(BotError::Illegal(reason))
+  }
+
+  /// Tells the bot a match is starting. Replies left over from an earlier match are thrown away.
+  pub fn start(&mut self, color: Color, state: &GameState) -> Result<(), BotError> {
+    while self.incoming.try_recv().is_ok() {}
+    self.send(&Message::Start(color, state.clone()))
+  }
+
+  /// Asks the bot for its move.
+  pub fn choose_move(&mut self, state: &GameState) -> Result<Move, BotError> {
+    self.send(&Message::State(state.clone()))?;
+    self.ask(|message| match message {
+      Message::Move(mv, _) if state.is_legal(mv) => Ok(mv),
+      Message::Move(..) => Err("that move is not legal".to_string()),
+      _ => Err("a move is needed".to_string())
+    })
+  }
+
+  /// Asks the bot which card to battle first.
+  pub fn choose_first(&mut self, targets: &[(usize, usize)]) -> Result<(usize, usize), BotError> {
+    self.send(&Message::Choose(targets.to_vec()))?;
+    self.ask(|message| match message {
+      Message::First(first) if targets.contains(&first) => Ok(first),
+      Message::First(_) => Err("that card can't be battled".to_string()),
+      _ => Err("a card to battle first is needed".to_string())
+    })
+  }
+
+  /// Tells the bot what happened after a placement.
+  pub fn tell(&mut self, color: Color, mv: Move, card: Card, report: &PlacementReport) -> Result<(), BotError> {
+    self.send(&Message::Placed(color, mv, card))?;
+    for battle in &report.battles {
+      self.send(&Message::Battle(battle.clone()))?;
+    }
+    if !report.taken.is_empty() {
+      self.send(&Message::Taken(report.taken.clone()))?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for Bot {
+  fn drop(&mut self) {
+    let _ = self.send(&Message::Bye);
+    self.stdin = None;
+    let deadline = Instant::now() + Duration::from_millis(EXIT_GRACE);
+    while Instant::now() < deadline {
+      match self.child.try_wait() {
+        Ok(None) => thread::sleep(Duration::from_millis(10)),
+        _ => return
+      }
+    }
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixtures::new_match;
+
+  use std::env;
+  use std::fs::{self, File};
+
+  /// Launches a shell script as a bot.
+  fn script(name: &str, body: &str) -> Bot {
+    let path = env::temp_dir().join(format!("tm-bot-{}.sh", name));
+    File::create(&path).and_then(|mut f| f.write_all(body.as_bytes())).unwrap();
+    Bot::launch(&format!("sh {}", path.display())).unwrap()
+  }
+
+  #[test]
+  fn silent_bots_time_out() {
+    let state = new_match(1);
+    let mut bot = script("silent", "while read line; do :; done\n").timeout(Duration::from_millis(200));
+    bot.start(Color::Blue, &state).unwrap();
+    match bot.choose_move(&state) {
+      Err(BotError::Timeout) => {},
+      result => panic!("{:?} isn't a timeout", result)
+    }
+  }
+
+  #[test]
+  fn bad_replies_forfeit() {
+    let state = new_match(2);
+    let body = "while read line; do\n  case \"$line\" in\n    state*) echo \"move 9 9 9\";;\n    illegal*) echo \"what\";;\n  esac\ndone\n";
+    let mut bot = script("bad", body).timeout(Duration::from_secs(5));
+    bot.start(Color::Blue, &state).unwrap();
+    match bot.choose_move(&state) {
+      Err(BotError::Illegal(ref reason)) => assert_eq!(reason, "that is not a message"),
+      result => panic!("{:?} isn't a forfeit", result)
+    }
+  }
+
+  #[test]
+  fn bots_exit_once_their_input_closes() {
+    let done = env::temp_dir().join("tm-bot-done");
+    let _ = fs::remove_file(&done);
+    let bot = script("exits", &format!("while read line; do :; done\necho bye > {}\n", done.display()));
+    drop(bot);
+    assert!(done.exists(), "The bot was killed before it could finish");
+  }
+
+  #[test]
+  fn proper_bots_play() {
+    let state = new_match(3);
+    let mv = state.legal_moves()[0];
+    // The bot plays the given move and battles the first card it is offered.
+    let body = format!("while read line; do\n  case \"$line\" in\n    state*) echo \"{}\";;\n    choose*) set -- $line; echo \"first $2\";;\n  esac\ndone\n", Message::Move(mv, None).to_string());
+    let mut bot = script("proper", &body).timeout(Duration::from_secs(5));
+    bot.start(Color::Blue, &state).unwrap();
+    assert_eq!(bot.choose_move(&state).unwrap(), mv);
+    assert_eq!(bot.choose_first(&[(2, 1), (1, 2)]).unwrap(), (2, 1));
+  }
+}
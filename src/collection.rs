@@ -0,0 +1,198 @@
+use {Board, Card, Color, Space, TetraMaster, Arrows};
+use score::MatchResult;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The number of different card types in the game.
+pub const CARD_TYPES: u8 = 100;
+
+/// The most cards a player can hold in their collection.
+pub const MAX_CARDS: usize = 100;
+
+/// Where the player's collection is saved by default.
+pub fn default_path() -> PathBuf {
+  let base = env::var_os("APPDATA")
+    .or_else(|| env::var_os("HOME"))
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from("."));
+  base.join(if cfg!(windows) { "Tetra Master" } else { ".tetra_master" }).join("collection.txt")
+}
+
+/// A card owned by a player, as stored in their collection.
+#[derive(Debug, Clone)]
+pub struct CollectedCard {
+  /// The card type, between 0 and `CARD_TYPES - 1`.
+  pub id: u8,
+  pub card: Card,
+  /// How much experience the card has gained from battles.
+  pub growth: u16
+}
+
+impl CollectedCard {
+  pub fn new(id: u8, card: Card) -> Self {
+    CollectedCard {
+      id: id,
+      card: card,
+      growth: 0
+    }
+  }
+
+  /// Parses a line in the collection format.
+  ///
+  /// The format is the card type, the card's stats, its arrow flags in hex and its growth,
+  /// separated by whitespace (e.g. `12 1M23 a5 0`).
+  pub fn parse(line: &str) -> Option<CollectedCard> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() != 4 {
+      return None;
+    }
+    let id = match parts[0].parse::<u8>() {
+      Ok(x) if x < CARD_TYPES => x,
+      _ => return None
+    };
+    let mut card = match TetraMaster::parse_card(parts[1]) {
+      Some(c) => c,
+      None => return None
+    };
+    card.arrows = match u8::from_str_radix(parts[2], 16) {
+      Ok(x) => Arrows::from_flags(x),
+      Err(_) => return None
+    };
+    let growth = match parts[3].parse::<u16>() {
+      Ok(x) => x,
+      Err(_) => return None
+    };
+    Some(CollectedCard {
+      id: id,
+      card: card,
+      growth: growth
+    })
+  }
+}
+
+impl ToString for CollectedCard {
+  fn to_string(&self) -> String {
+    format!("{} {} {:02x} {}", self.id, self.card.to_string(), self.card.arrows.flags, self.growth)
+  }
+}
+
+/// A player's collection of cards, which persists between games.
+#[derive(Debug, Clone, Default)]
+pub struct Collection {
+  pub cards: Vec<CollectedCard>
+}
+
+impl Collection {
+  pub fn new() -> Self {
+    Collection::default()
+  }
+
+  /// Parses a collection, one card per line. Blank lines and lines starting with `#` are ignored.
+  pub fn parse(contents: &str) -> Option<Collection> {
+    let mut cards = Vec::new();
+    for line in contents.lines().map(|x| x.trim()) {
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      match CollectedCard::parse(line) {
+        Some(c) => cards.push(c),
+        None => return None
+      }
+    }
+    Some(Collection {
+      cards: cards
+    })
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Collection> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    Collection::parse(&contents).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid collection"))
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "# id stats arrows growth")?;
+    for card in &self.cards {
+      writeln!(file, "{}", card.to_string())?;
+    }
+    Ok(())
+  }
+
+  pub fn len(&self) -> usize {
+    self.cards.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.cards.is_empty()
+  }
+
+  pub fn is_full(&self) -> bool {
+    self.cards.len() >= MAX_CARDS
+  }
+
+  /// Adds a card to the collection, returning false if the collection is full.
+  pub fn add(&mut self, card: CollectedCard) -> bool {
+    if self.is_full() {
+      return false;
+    }
+    self.cards.push(card);
+    true
+  }
+
+  pub fn remove(&mut self, index: usize) -> CollectedCard {
+    self.cards.remove(index)
+  }
+}
+
+/// What is at stake in a match.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stakes {
+  /// The winner takes one of the loser's flipped cards, or all of the loser's cards on a perfect.
+  Capture,
+  /// A practice match. No cards change hands.
+  Practice
+}
+
+impl Default for Stakes {
+  fn default() -> Self {
+    Stakes::Capture
+  }
+}
+
+/// The cards the winner of a match is owed by the loser, by their position on the board.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Spoils {
+  Nothing,
+  /// The winner picks one of these cards.
+  Choose(Vec<(usize, usize)>),
+  /// The winner owns every card on the board and takes all of these.
+  Perfect(Vec<(usize, usize)>)
+}
+
+impl Stakes {
+  /// Determines what the winner takes from the loser on a finished board.
+  pub fn spoils(&self, board: &Board, winner: Color) -> Spoils {
+    if *self == Stakes::Practice {
+      return Spoils::Nothing;
+    }
+    let taken: Vec<(usize, usize)> = board.spaces
+      .iter()
+      .flat_map(|x| x.iter())
+      .filter_map(|s| match *s {
+        Space::Card(ref c) if c.color == winner && c.original_color != winner => Some((c.row, c.column)),
+        _ => None
+      })
+      .collect();
+    if taken.is_empty() {
+      Spoils::Nothing
+    } else if board.score().result_for(winner) == MatchResult::Perfect {
+      Spoils::Perfect(taken)
+    } else {
+      Spoils::Choose(taken)
+    }
+  }
+}
@@ -0,0 +1,166 @@
+use Class;
+use collection::{CollectedCard, CARD_TYPES};
+
+use std::collections::HashSet;
+
+/// Points awarded for each different card type owned.
+pub const TYPE_POINTS: u16 = 10;
+/// Points awarded for each different arrow pattern owned.
+pub const ARROW_POINTS: u16 = 5;
+/// Points awarded for each card type owned with the Flexible (X) class.
+pub const FLEXIBLE_POINTS: u16 = 1;
+/// Points awarded for each card type owned with the Assault (A) class.
+pub const ASSAULT_POINTS: u16 = 2;
+
+/// A player's collector points, broken down by where they came from.
+///
+/// Each card type owned is worth 10 points and each different arrow pattern is worth 5 points. A
+/// card type owned as an Assault card is worth 2 more points, or 1 more point if the best owned is
+/// a Flexible card. With the 100 card limit, a perfect collection is worth 1700 points.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CollectorPoints {
+  pub types: u16,
+  pub arrow_patterns: u16,
+  pub flexible_types: u16,
+  pub assault_types: u16
+}
+
+impl CollectorPoints {
+  pub fn calculate(cards: &[CollectedCard]) -> Self {
+    let mut types = HashSet::new();
+    let mut patterns = HashSet::new();
+    let mut flexible = HashSet::new();
+    let mut assault = HashSet::new();
+    for card in cards {
+      types.insert(card.id);
+      patterns.insert(card.card.arrows.flags);
+      match card.card.class {
+        Class::Flexible => { flexible.insert(card.id); },
+        Class::Assault => { assault.insert(card.id); },
+        _ => {}
+      }
+    }
+    CollectorPoints {
+      types: types.len() as u16,
+      arrow_patterns: patterns.len() as u16,
+      flexible_types: flexible.difference(&assault).count() as u16,
+      assault_types: assault.len() as u16
+    }
+  }
+
+  pub fn type_points(&self) -> u16 {
+    self.types * TYPE_POINTS
+  }
+
+  pub fn arrow_points(&self) -> u16 {
+    self.arrow_patterns * ARROW_POINTS
+  }
+
+  pub fn class_points(&self) -> u16 {
+    self.flexible_types * FLEXIBLE_POINTS + self.assault_types * ASSAULT_POINTS
+  }
+
+  pub fn total(&self) -> u16 {
+    self.type_points() + self.arrow_points() + self.class_points()
+  }
+
+  pub fn rank(&self) -> Rank {
+    Rank::from_points(self.total())
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Rank {
+  Beginner,
+  Player,
+  Senior,
+  Fighter,
+  Veteran,
+  Analyst,
+  Expert,
+  Professional,
+  Commander,
+  Leader,
+  Dominator,
+  Noble,
+  Duke,
+  King,
+  Emperor,
+  Master
+}
+
+const RANKS: &'static [(u16, Rank)] = &[
+  (0, Rank::Beginner),
+  (300, Rank::Player),
+  (400, Rank::Senior),
+  (500, Rank::Fighter),
+  (600, Rank::Veteran),
+  (700, Rank::Analyst),
+  (800, Rank::Expert),
+  (900, Rank::Professional),
+  (1000, Rank::Commander),
+  (1100, Rank::Leader),
+  (1250, Rank::Dominator),
+  (1300, Rank::Noble),
+  (1400, Rank::Duke),
+  (1500, Rank::King),
+  (1600, Rank::Emperor),
+  (1700, Rank::Master)
+];
+
+impl Rank {
+  pub fn from_points(points: u16) -> Rank {
+    RANKS.iter()
+      .rev()
+      .find(|&&(min, _)| points >= min)
+      .map(|&(_, rank)| rank)
+      .unwrap_or(Rank::Beginner)
+  }
+
+  /// The fewest collector points needed for this rank.
+  pub fn min_points(&self) -> u16 {
+    RANKS.iter().find(|&&(_, rank)| rank == *self).map(|&(min, _)| min).unwrap()
+  }
+
+  pub fn next(&self) -> Option<Rank> {
+    let i = RANKS.iter().position(|&(_, rank)| rank == *self).unwrap();
+    RANKS.get(i + 1).map(|&(_, rank)| rank)
+  }
+
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Rank::Beginner => "Beginner",
+      Rank::Player => "Player",
+      Rank::Senior => "Senior",
+      Rank::Fighter => "Fighter",
+      Rank::Veteran => "Veteran",
+      Rank::Analyst => "Analyst",
+      Rank::Expert => "Expert",
+      Rank::Professional => "Professional",
+      Rank::Commander => "Commander",
+      Rank::Leader => "Leader",
+      Rank::Dominator => "Dominator",
+      Rank::Noble => "Noble",
+      Rank::Duke => "Duke",
+      Rank::King => "King",
+      Rank::Emperor => "Emperor",
+      Rank::Master => "Master"
+    }
+  }
+}
+
+/// Card types that are not in the given cards.
+pub fn missing_types(cards: &[CollectedCard]) -> Vec<u8> {
+  let owned: HashSet<u8> = cards.iter().map(|c| c.id).collect();
+  (0..CARD_TYPES).filter(|id| !owned.contains(id)).collect()
+}
+
+/// Card types in the given cards that are not yet owned as an Assault card.
+pub fn missing_assault(cards: &[CollectedCard]) -> Vec<u8> {
+  let mut owned: Vec<u8> = cards.iter().map(|c| c.id).collect();
+  owned.sort();
+  owned.dedup();
+  owned.into_iter()
+    .filter(|&id| !cards.iter().any(|c| c.id == id && match c.card.class { Class::Assault => true, _ => false }))
+    .collect()
+}
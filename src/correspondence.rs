@@ -0,0 +1,668 @@
+use {Board, Card, Color};
+use fair::{from_hex, hmac_sha256, placement_rng, sha256, to_hex, ChainVerifier, Seed, SeedChain};
+use lobby::rules_allowed;
+use net::Rules;
+use notation::{format_board, format_color, format_hand, format_move, format_position, format_state, parse_board, parse_color, parse_hand, parse_move, parse_position, parse_state};
+use state::{GameState, Move};
+
+use rand::Rng;
+
+/// The version of the file formats written, which must match to open a file.
+pub const FILE_VERSION: u32 = 1;
+
+/// A placement in a game file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turn {
+  pub color: Color,
+  pub mv: Move,
+  /// The card the player picked to battle first, if they could pick.
+  pub first: Option<(usize, usize)>
+}
+
+/// What has to happen next in a game file, and who has to do it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waiting {
+  /// Red has to join with their cards.
+  Join,
+  /// Blue has to show the cards they opened the game with.
+  Hand,
+  /// The player has to reveal their seed for the last placement, which the other player made.
+  Seed(Color),
+  /// The player has to move.
+  Move(Color),
+  Over
+}
+
+impl Waiting {
+  /// The player who has to act next, if anyone.
+  pub fn player(&self) -> Option<Color> {
+    match *self {
+      Waiting::Join => Some(Color::Red),
+      Waiting::Hand => Some(Color::Blue),
+      Waiting::Seed(color) | Waiting::Move(color) => Some(color),
+      Waiting::Over => None
+    }
+  }
+}
+
+/// A game file played out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+  /// The match after every placement whose battles could be rolled.
+  pub state: GameState,
+  /// The last placement, if its battles can't be rolled until the other player reveals their seed.
+  pub pending: Option<Turn>,
+  pub waiting: Waiting
+}
+
+/// A match played by passing a file back and forth, with no server.
+///
+/// Blue opens the game with the rules, the board, a hash of their cards and the first link of
+/// their seed chain. Red joins with their cards and the first link of their chain, and blue then
+/// shows their cards, so neither player picks their cards knowing the other's. Who moves first
+/// comes from both players' secrets, so neither can pick it.
+///
+/// Each placement is rolled with a seed from each player, as in network play. The player moving
+/// reveals their seed with the move, and the other player reveals theirs before they move in turn,
+/// so neither knows how the battles will go when they commit to them.
+///
+/// The file is signed with a key both players know, and ends with the match as it stands. A file
+/// is only opened if its signature, its seeds and its placements all check out, and its
+/// placements replay to that match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameFile {
+  pub rules: Rules,
+  pub board: Board,
+  blue_commitment: Seed,
+  blue_hand_hash: Seed,
+  /// Red's hand and the first link of their chain.
+  red: Option<(Vec<Card>, Seed)>,
+  /// Blue's hand once shown, and the salt it was hashed with.
+  blue_hand: Option<(Vec<Card>, Seed)>,
+  turns: Vec<Turn>,
+  /// The seeds each player has revealed, blue's first, in order of placement.
+  seeds: [Vec<Seed>; 2]
+}
+
+fn color_index(color: Color) -> usize {
+  match color {
+    Color::Blue => 0,
+    Color::Red => 1
+  }
+}
+
+fn hand_hash(hand: &[Card], salt: &Seed) -> Seed {
+  sha256(&[format_hand(hand).as_bytes(), salt])
+}
+
+/// The salt a player's hand is hashed with, which only they can work out before it is shown.
+fn hand_salt(secret: &Seed) -> Seed {
+  sha256(&[secret, b"hand"])
+}
+
+impl GameFile {
+  /// Opens a game as blue. `secret` is the secret of blue's seed chain, kept in their `Side`.
+  pub fn open(rules: Rules, board: Board, hand: &[Card], secret: &Seed) -> Result<GameFile, String> {
+    if !rules_allowed(&rules) || board.width() != rules.width || board.height() != rules.height {
+      return Err("those rules aren't allowed".to_string());
+    }
+    if hand.len() != rules.hand_size {
+      return Err(format!("{} cards are needed", rules.hand_size));
+    }
+    let chain = SeedChain::from_secret(*secret, rules.width * rules.height);
+    Ok(GameFile {
+      rules: rules,
+      board: board,
+      blue_commitment: chain.commitment(),
+      blue_hand_hash: hand_hash(hand, &hand_salt(secret)),
+      red: None,
+      blue_hand: None,
+      turns: Vec::new(),
+      seeds: [Vec::new(), Vec::new()]
+    })
+  }
+
+  fn chain(&self, secret: &Seed) -> SeedChain {
+    SeedChain::from_secret(*secret, self.rules.width * self.rules.height)
+  }
+
+  /// Joins the game as red.
+  pub fn join(&mut self, hand: Vec<Card>, secret: &Seed) -> Result<(), String> {
+    if self.red.is_some() {
+      return Err("red has already joined".to_string());
+    }
+    if hand.len() != self.rules.hand_size {
+      return Err(format!("{} cards are needed", self.rules.hand_size));
+    }
+    let commitment = self.chain(secret).commitment();
+    self.red = Some((hand, commitment));
+    Ok(())
+  }
+
+  /// Shows blue's hand, once red has joined.
+  pub fn show_hand(&mut self, hand: Vec<Card>, secret: &Seed) -> Result<(), String> {
+    if self.replay()?.waiting != Waiting::Hand {
+      return Err("your cards can't be shown now".to_string());
+    }
+    let salt = hand_salt(secret);
+    if hand_hash(&hand, &salt) != self.blue_hand_hash {
+      return Err("those aren't the cards the game was opened with".to_string());
+    }
+    self.blue_hand = Some((hand, salt));
+    Ok(())
+  }
+
+  /// Reveals a player's seed for the last placement, made by the other player.
+  pub fn reveal(&mut self, color: Color, secret: &Seed) -> Result<(), String> {
+    if self.replay()?.waiting != Waiting::Seed(color) {
+      return Err("there is no seed to reveal".to_string());
+    }
+    let placement = self.turns.len();
+    let seed = self.chain(secret).reveal(placement).ok_or("the seed chain is too short")?;
+    self.seeds[color_index(color)].push(seed);
+    Ok(())
+  }
+
+  /// Makes a placement, revealing the player's seed for it.
+  pub fn play(&mut self, color: Color, mv: Move, first: Option<(usize, usize)>, secret: &Seed) -> Result<(), String> {
+    let replay = self.replay()?;
+    if replay.waiting != Waiting::Move(color) {
+      return Err("it is not your turn".to_string());
+    }
+    check_turn(&replay.state, color, mv, first)?;
+    let placement = self.turns.len() + 1;
+    let seed = self.chain(secret).reveal(placement).ok_or("the seed chain is too short")?;
+    self.turns.push(Turn {
+      color: color,
+      mv: mv,
+      first: first
+    });
+    self.seeds[color_index(color)].push(seed);
+    Ok(())
+  }
+
+  /// Plays out the game, checking every hand, seed and placement.
+  pub fn replay(&self) -> Result<Replay, String> {
+    let (red_hand, red_commitment) = match self.red {
+      Some((ref hand, commitment)) => (hand.clone(), commitment),
+      None => {
+        if !self.turns.is_empty() || self.blue_hand.is_some() {
+          return Err("the game was played before red joined".to_string());
+        }
+        return Ok(Replay {
+          state: GameState::new(self.board.clone(), Vec::new(), Vec::new(), Color::Blue),
+          pending: None,
+          waiting: Waiting::Join
+        });
+      }
+    };
+    let (blue_hand, salt) = match self.blue_hand {
+      Some((ref hand, salt)) => (hand.clone(), salt),
+      None => {
+        if !self.turns.is_empty() {
+          return Err("the game was played before blue showed their cards".to_string());
+        }
+        return Ok(Replay {
+          state: GameState::new(self.board.clone(), Vec::new(), red_hand, Color::Blue),
+          pending: None,
+          waiting: Waiting::Hand
+        });
+      }
+    };
+    if hand_hash(&blue_hand, &salt) != self.blue_hand_hash {
+      return Err("blue's cards aren't the ones the game was opened with".to_string());
+    }
+    if blue_hand.len() != self.rules.hand_size || red_hand.len() != self.rules.hand_size {
+      return Err(format!("{} cards are needed", self.rules.hand_size));
+    }
+    let to_move = if sha256(&[&salt, &red_commitment])[0] & 1 == 0 { Color::Blue } else { Color::Red };
+    let mut state = GameState::new(self.board.clone(), blue_hand, red_hand, to_move);
+    let mut verifiers = [ChainVerifier::new(self.blue_commitment), ChainVerifier::new(red_commitment)];
+    for (i, seeds) in self.seeds.iter().enumerate() {
+      if seeds.len() > self.turns.len() {
+        return Err("a seed was revealed for a placement that wasn't made".to_string());
+      }
+      for (p, &seed) in seeds.iter().enumerate() {
+        verifiers[i].check(p + 1, seed)?;
+      }
+    }
+    let mut pending = None;
+    for (i, turn) in self.turns.iter().enumerate() {
+      let placement = i + 1;
+      check_turn(&state, turn.color, turn.mv, turn.first).map_err(|e| format!("placement {}: {}", placement, e))?;
+      match (verifiers[0].get(placement), verifiers[1].get(placement)) {
+        (Some(blue), Some(red)) => {
+          state.apply_reported(turn.mv, turn.first, &mut placement_rng(&blue, &red)).expect("Move was checked");
+        },
+        (blue, red) => {
+          let own = if turn.color == Color::Blue { blue } else { red };
+          if own.is_none() || placement != self.turns.len() {
+            return Err(format!("placement {} is missing a seed", placement));
+          }
+          pending = Some(*turn);
+        }
+      }
+    }
+    let waiting = match pending {
+      Some(turn) => Waiting::Seed(turn.color.opponent()),
+      None if state.is_over() => Waiting::Over,
+      None => Waiting::Move(state.to_move)
+    };
+    Ok(Replay {
+      state: state,
+      pending: pending,
+      waiting: waiting
+    })
+  }
+
+  /// The lines of the file, leaving out the match and the signature at the end.
+  fn history(&self) -> Vec<String> {
+    let mut lines = vec![
+      format!("tetra-master-game {}", FILE_VERSION),
+      format!("rules {} {} {}", self.rules.width, self.rules.height, self.rules.hand_size),
+      format!("board {}", format_board(&self.board)),
+      format!("open {} {}", to_hex(&self.blue_commitment), to_hex(&self.blue_hand_hash))
+    ];
+    if let Some((ref hand, commitment)) = self.red {
+      lines.push(format!("join {} {}", format_hand(hand), to_hex(&commitment)));
+    }
+    if let Some((ref hand, salt)) = self.blue_hand {
+      lines.push(format!("hand {} {}", format_hand(hand), to_hex(&salt)));
+    }
+    for (i, turn) in self.turns.iter().enumerate() {
+      let placement = i + 1;
+      let mut line = format!("turn {} {} {}", placement, format_color(turn.color), format_move(&turn.mv));
+      if let Some(first) = turn.first {
+        line.push_str(&format!(" first {}", format_position(first)));
+      }
+      lines.push(line);
+      // The player moving reveals their seed first.
+      for &color in &[turn.color, turn.color.opponent()] {
+        if let Some(seed) = self.seeds[color_index(color)].get(i) {
+          lines.push(format!("seed {} {} {}", placement, format_color(color), to_hex(seed)));
+        }
+      }
+    }
+    lines
+  }
+
+  /// Writes the file, signed with the key.
+  pub fn to_text(&self, key: &str) -> Result<String, String> {
+    let mut lines = self.history();
+    let replay = self.replay()?;
+    if self.blue_hand.is_some() {
+      lines.push(format!("state {}", format_state(&replay.state)));
+    }
+    let signature = hmac_sha256(key.as_bytes(), lines.join("\n").as_bytes());
+    lines.push(format!("signature {}", to_hex(&signature)));
+    Ok(lines.join("\n") + "\n")
+  }
+
+  /// Reads a file, checking its signature with the key, and that it replays to the match it ends
+  /// with.
+  pub fn parse(text: &str, key: &str) -> Result<GameFile, String> {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    let (signature, signed) = match lines.split_last() {
+      Some((last, signed)) if last.starts_with("signature ") => (from_hex(&last["signature ".len()..]), signed),
+      _ => return Err("the file isn't signed".to_string())
+    };
+    if signature != Some(hmac_sha256(key.as_bytes(), signed.join("\n").as_bytes())) {
+      return Err("the signature doesn't match, so the file was changed or the key is wrong".to_string());
+    }
+    if signed.first() != Some(&&format!("tetra-master-game {}", FILE_VERSION)[..]) {
+      return Err(format!("only version {} game files can be opened", FILE_VERSION));
+    }
+    let invalid = |line: &str| format!("this line is invalid: {}", line);
+    // The words of a line at the top of the file, which must start with the given name.
+    let field = |i: usize, name: &str, count: usize| match signed.get(i) {
+      Some(line) if line.split(' ').next() == Some(name) => {
+        let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+        if words.len() == count { Ok(words) } else { Err(invalid(line)) }
+      },
+      _ => Err(format!("the {} line is missing", name))
+    };
+    let words = field(1, "rules", 3)?;
+    let rules = match (words[0].parse(), words[1].parse(), words[2].parse()) {
+      (Ok(width), Ok(height), Ok(hand_size)) => Rules {
+        width: width,
+        height: height,
+        hand_size: hand_size
+      },
+      _ => return Err(invalid(signed[1]))
+    };
+    let board = parse_board(field(2, "board", 1)?[0]).ok_or_else(|| invalid(signed[2]))?;
+    let words = field(3, "open", 2)?;
+    let (blue_commitment, blue_hand_hash) = match (from_hex(words[0]), from_hex(words[1])) {
+      (Some(c), Some(h)) => (c, h),
+      _ => return Err(invalid(signed[3]))
+    };
+    if !rules_allowed(&rules) || board.width() != rules.width || board.height() != rules.height {
+      return Err("those rules aren't allowed".to_string());
+    }
+    let mut file = GameFile {
+      rules: rules,
+      board: board,
+      blue_commitment: blue_commitment,
+      blue_hand_hash: blue_hand_hash,
+      red: None,
+      blue_hand: None,
+      turns: Vec::new(),
+      seeds: [Vec::new(), Vec::new()]
+    };
+    let mut claimed = None;
+    for line in signed.iter().skip(4) {
+      let words: Vec<&str> = line.split_whitespace().collect();
+      match (words[0], words.len()) {
+        ("join", 3) if file.red.is_none() => match (parse_hand(words[1]), from_hex(words[2])) {
+          (Some(hand), Some(commitment)) => file.red = Some((hand, commitment)),
+          _ => return Err(invalid(line))
+        },
+        ("hand", 3) if file.blue_hand.is_none() => match (parse_hand(words[1]), from_hex(words[2])) {
+          (Some(hand), Some(salt)) => file.blue_hand = Some((hand, salt)),
+          _ => return Err(invalid(line))
+        },
+        ("turn", 6) | ("turn", 8) => {
+          let placement = words[1].parse::<usize>().ok();
+          let color = parse_color(words[2]);
+          let mv = parse_move(&words[3..6].join(" "));
+          let first = if words.len() == 8 && words[6] == "first" { parse_position(words[7]).map(Some) } else { Some(None) };
+          match (placement, color, mv, first) {
+            (Some(p), Some(color), Some(mv), Some(first)) if p == file.turns.len() + 1 => file.turns.push(Turn {
+              color: color,
+              mv: mv,
+              first: first
+            }),
+            _ => return Err(invalid(line))
+          }
+        },
+        ("seed", 4) => match (words[1].parse::<usize>(), parse_color(words[2]), from_hex(words[3])) {
+          (Ok(p), Some(color), Some(seed)) if p == file.seeds[color_index(color)].len() + 1 => file.seeds[color_index(color)].push(seed),
+          _ => return Err(invalid(line))
+        },
+        ("state", _) if claimed.is_none() => match parse_state(&words[1..].join(" ")) {
+          Some(state) => claimed = Some(state),
+          None => return Err(invalid(line))
+        },
+        _ => return Err(invalid(line))
+      }
+    }
+    // Rewriting the file must give back exactly what was signed, so nothing in it goes unchecked.
+    let mut rewritten = file.history();
+    let replay = file.replay()?;
+    if let Some(state) = claimed {
+      if state != replay.state {
+        return Err("the placements don't replay to the match the file ends with".to_string());
+      }
+      rewritten.push(format!("state {}", format_state(&state)));
+    }
+    if rewritten != signed {
+      return Err("the file is out of order".to_string());
+    }
+    Ok(file)
+  }
+
+  /// A digest of everything in the file so far, to tell later whether it was changed.
+  pub fn digest(&self) -> (usize, Seed) {
+    let history = self.history();
+    (history.len(), sha256(&[history.join("\n").as_bytes()]))
+  }
+
+  /// Whether this file carries on from the file with the given digest, without changing it.
+  pub fn continues(&self, digest: (usize, Seed)) -> bool {
+    let history = self.history();
+    let (length, hash) = digest;
+    length <= history.len() && sha256(&[history[..length].join("\n").as_bytes()]) == hash
+  }
+}
+
+/// Checks a placement is allowed, including the card picked to battle first.
+fn check_turn(state: &GameState, color: Color, mv: Move, first: Option<(usize, usize)>) -> Result<(), String> {
+  if state.to_move != color {
+    return Err(format!("it was {}'s turn", format_color(state.to_move)));
+  }
+  if !state.is_legal(mv) {
+    return Err(format!("{} is not a legal move", format_move(&mv)));
+  }
+  let targets = state.battle_targets(mv);
+  match first {
+    Some(first) if targets.len() < 2 || !targets.contains(&first) => Err(format!("{} can't be picked to battle first", format_position(first))),
+    None if targets.len() > 1 => {
+      let targets: Vec<String> = targets.into_iter().map(format_position).collect();
+      Err(format!("pick which card to battle first: {}", targets.join(" ")))
+    },
+    _ => Ok(())
+  }
+}
+
+/// What one player keeps to themselves about a game file: their secret, the key, and what the file
+/// looked like when they last sent it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Side {
+  pub color: Color,
+  pub key: String,
+  /// The secret their seed chain grows from.
+  pub secret: Seed,
+  /// The cards they brought.
+  pub hand: Vec<Card>,
+  /// The digest of the file they last wrote.
+  pub sent: Option<(usize, Seed)>
+}
+
+impl Side {
+  pub fn new<R: Rng>(color: Color, key: &str, hand: Vec<Card>, rng: &mut R) -> Self {
+    let mut secret = [0; 32];
+    rng.fill_bytes(&mut secret);
+    Side {
+      color: color,
+      key: key.to_string(),
+      secret: secret,
+      hand: hand,
+      sent: None
+    }
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut text = format!("tetra-master-side {}\ncolor {}\nkey {}\nsecret {}\nhand {}\n",
+      FILE_VERSION,
+      format_color(self.color),
+      self.key,
+      to_hex(&self.secret),
+      format_hand(&self.hand));
+    if let Some((length, hash)) = self.sent {
+      text.push_str(&format!("sent {} {}\n", length, to_hex(&hash)));
+    }
+    text
+  }
+
+  pub fn parse(text: &str) -> Option<Side> {
+    let mut color = None;
+    let mut key = None;
+    let mut secret = None;
+    let mut hand = None;
+    let mut sent = None;
+    let mut lines = text.lines();
+    if lines.next() != Some(&format!("tetra-master-side {}", FILE_VERSION)[..]) {
+      return None;
+    }
+    for line in lines {
+      let mut parts = line.splitn(2, ' ');
+      let value = parts.nth(1).unwrap_or("");
+      match line.split(' ').next() {
+        Some("color") => color = parse_color(value),
+        Some("key") => key = Some(value.to_string()),
+        Some("secret") => secret = from_hex(value),
+        Some("hand") => hand = parse_hand(value),
+        Some("sent") => {
+          let words: Vec<&str> = value.split(' ').collect();
+          sent = match (words.first().and_then(|w| w.parse().ok()), words.get(1).and_then(|w| from_hex(w))) {
+            (Some(length), Some(hash)) => Some((length, hash)),
+            _ => return None
+          };
+        },
+        _ => return None
+      }
+    }
+    match (color, key, secret, hand) {
+      (Some(color), Some(key), Some(secret), Some(hand)) => Some(Side {
+        color: color,
+        key: key,
+        secret: secret,
+        hand: hand,
+        sent: sent
+      }),
+      _ => None
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use seeded_rng;
+  use fixtures::hand;
+  use generator::BoardGenerator;
+
+  const KEY: &'static str = "a key both players know";
+
+  /// A game blue opened, red joined and blue showed their cards for. Returns the file and blue's
+  /// and red's secrets.
+  fn started(seed: u64) -> (GameFile, Seed, Seed) {
+    let mut rng = seeded_rng(seed);
+    let (blue, red): (Seed, Seed) = (rng.gen(), rng.gen());
+    let blue_hand = hand(&mut rng, 5);
+    let board = BoardGenerator::new().seed(seed).generate();
+    let mut file = GameFile::open(Rules::default(), board, &blue_hand, &blue).unwrap();
+    file.join(hand(&mut rng, 5), &red).unwrap();
+    file.show_hand(blue_hand, &blue).unwrap();
+    (file, blue, red)
+  }
+
+  /// Has the player who has to act reveal their seed, or play the given legal move, battling the
+  /// first card they can.
+  fn step(file: &mut GameFile, blue: &Seed, red: &Seed, choice: usize) {
+    let replay = file.replay().unwrap();
+    let secret = |color| if color == Color::Blue { blue } else { red };
+    match replay.waiting {
+      Waiting::Seed(color) => file.reveal(color, secret(color)).unwrap(),
+      Waiting::Move(color) => {
+        let moves = replay.state.legal_moves();
+        let mv = moves[choice % moves.len()];
+        let targets = replay.state.battle_targets(mv);
+        let first = if targets.len() > 1 { Some(targets[0]) } else { None };
+        file.play(color, mv, first, secret(color)).unwrap();
+      },
+      waiting => panic!("Nothing can be done while {:?}", waiting)
+    }
+  }
+
+  /// Changes the lines of a file and signs it again, as someone who knows the key could.
+  fn resigned<F: Fn(&mut Vec<String>)>(text: &str, edit: F) -> String {
+    let mut lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+    lines.pop();
+    edit(&mut lines);
+    let signature = hmac_sha256(KEY.as_bytes(), lines.join("\n").as_bytes());
+    lines.push(format!("signature {}", to_hex(&signature)));
+    lines.join("\n") + "\n"
+  }
+
+  fn line(lines: &[String], start: &str) -> usize {
+    lines.iter().position(|l| l.starts_with(start)).expect("The line is in the file")
+  }
+
+  #[test]
+  fn games_round_trip_until_over() {
+    let mut rng = seeded_rng(1);
+    let (blue, red): (Seed, Seed) = (rng.gen(), rng.gen());
+    let blue_hand = hand(&mut rng, 5);
+    let mut file = GameFile::open(Rules::default(), BoardGenerator::new().seed(1).generate(), &blue_hand, &blue).unwrap();
+    assert_eq!(GameFile::parse(&file.to_text(KEY).unwrap(), KEY), Ok(file.clone()));
+    file.join(hand(&mut rng, 5), &red).unwrap();
+    assert_eq!(GameFile::parse(&file.to_text(KEY).unwrap(), KEY), Ok(file.clone()));
+    file.show_hand(blue_hand, &blue).unwrap();
+    let mut sent = file.digest();
+    while file.replay().unwrap().waiting != Waiting::Over {
+      step(&mut file, &blue, &red, 0);
+      let parsed = GameFile::parse(&file.to_text(KEY).unwrap(), KEY).unwrap();
+      assert_eq!(parsed, file);
+      assert!(parsed.continues(sent));
+      sent = parsed.digest();
+    }
+    let replay = file.replay().unwrap();
+    assert!(replay.state.is_over());
+    assert_eq!(replay.pending, None);
+  }
+
+  #[test]
+  fn edited_turns_are_refused() {
+    let (mut file, blue, red) = started(2);
+    let state = file.replay().unwrap().state;
+    // The first placement and both seeds for it.
+    step(&mut file, &blue, &red, 0);
+    step(&mut file, &blue, &red, 0);
+    let text = file.to_text(KEY).unwrap();
+    assert!(GameFile::parse(&resigned(&text, |_| {}), KEY).is_ok());
+    let played = state.legal_moves()[0];
+    let other = state.legal_moves().into_iter().find(|&mv| mv != played && state.battle_targets(mv).len() < 2).unwrap();
+    let edited = resigned(&text, |lines| {
+      let i = line(lines, "turn 1 ");
+      lines[i] = format!("turn 1 {} {}", format_color(state.to_move), format_move(&other));
+    });
+    assert!(GameFile::parse(&edited, KEY).is_err());
+  }
+
+  #[test]
+  fn wrong_or_reordered_seeds_are_refused() {
+    let (mut file, blue, red) = started(3);
+    for _ in 0..4 {
+      step(&mut file, &blue, &red, 0);
+    }
+    let text = file.to_text(KEY).unwrap();
+    let wrong = resigned(&text, |lines| {
+      let i = line(lines, "seed 2 ");
+      let mut words: Vec<String> = lines[i].split(' ').map(|w| w.to_string()).collect();
+      words[3] = to_hex(&[0xAB; 32]);
+      lines[i] = words.join(" ");
+    });
+    assert!(GameFile::parse(&wrong, KEY).is_err());
+    // The player moving reveals their seed first.
+    let reordered = resigned(&text, |lines| {
+      let i = line(lines, "seed 1 ");
+      lines.swap(i, i + 1);
+    });
+    assert!(GameFile::parse(&reordered, KEY).is_err());
+  }
+
+  #[test]
+  fn states_that_dont_replay_are_refused() {
+    let (mut file, blue, red) = started(4);
+    step(&mut file, &blue, &red, 0);
+    let earlier = file.to_text(KEY).unwrap();
+    for _ in 0..4 {
+      step(&mut file, &blue, &red, 0);
+    }
+    let text = file.to_text(KEY).unwrap();
+    let claimed = resigned(&text, |lines| {
+      let i = line(lines, "state ");
+      lines[i] = earlier.lines().find(|l| l.starts_with("state ")).unwrap().to_string();
+    });
+    assert!(GameFile::parse(&claimed, KEY).is_err());
+  }
+
+  #[test]
+  fn changed_history_does_not_continue() {
+    let (mut file, blue, red) = started(5);
+    let mut changed = file.clone();
+    for _ in 0..4 {
+      step(&mut file, &blue, &red, 0);
+    }
+    let sent = file.digest();
+    step(&mut file, &blue, &red, 0);
+    assert!(file.continues(sent));
+    // The same game with another first move, played on as far.
+    for _ in 0..5 {
+      step(&mut changed, &blue, &red, 1);
+    }
+    assert!(GameFile::parse(&changed.to_text(KEY).unwrap(), KEY).is_ok());
+    assert!(!changed.continues(sent));
+  }
+}
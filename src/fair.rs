@@ -0,0 +1,452 @@
+use seeded_rng;
+use net::Message;
+use notation::{format_move, format_position};
+use report::BattleReport;
+use state::{GameState, Move};
+
+use rand::{Rng, XorShiftRng};
+use sha2::{Digest, Sha256};
+
+/// A value revealed by one side to make up the rolls for one placement.
+pub type Seed = [u8; 32];
+
+pub fn sha256(parts: &[&[u8]]) -> Seed {
+  let mut hasher = Sha256::default();
+  for part in parts {
+    hasher.input(part);
+  }
+  let mut seed = [0; 32];
+  seed.copy_from_slice(&hasher.result());
+  seed
+}
+
+/// The HMAC-SHA256 of a message, which only someone who knows the key can make or check.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> Seed {
+  const BLOCK_SIZE: usize = 64;
+  let mut block = [0; BLOCK_SIZE];
+  if key.len() > BLOCK_SIZE {
+    block[..32].copy_from_slice(&sha256(&[key]));
+  } else {
+    block[..key.len()].copy_from_slice(key);
+  }
+  let inner_pad: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+  let outer_pad: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+  let inner = sha256(&[&inner_pad, message]);
+  sha256(&[&outer_pad, &inner])
+}
+
+pub fn to_hex(seed: &Seed) -> String {
+  seed.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(text: &str) -> Option<Seed> {
+  if text.len() != 64 || !text.is_ascii() {
+    return None;
+  }
+  let mut seed = [0; 32];
+  for (i, byte) in seed.iter_mut().enumerate() {
+    *byte = match u8::from_str_radix(&text[i * 2..i * 2 + 2], 16) {
+      Ok(b) => b,
+      Err(_) => return None
+    };
+  }
+  Some(seed)
+}
+
+/// The random number generator for the battles of one placement, made from both sides' seeds.
+///
+/// Neither side can pick the rolls, since each seed was fixed before the other was known.
+pub fn placement_rng(host: &Seed, joiner: &Seed) -> XorShiftRng {
+  let hash = sha256(&[host, joiner]);
+  let seed = hash[..8].iter().fold(0u64, |n, b| n << 8 | *b as u64);
+  seeded_rng(seed)
+}
+
+/// A chain of seeds, each the hash of the next, for one side of a match.
+///
+/// The first link is sent to the other side before the match starts. The rest are revealed one
+/// per placement, and each can be checked by hashing it to get the one before. Nobody can work out
+/// a link before it is revealed, and nobody can change one after the first link was sent.
+#[derive(Debug, Clone)]
+pub struct SeedChain {
+  links: Vec<Seed>
+}
+
+impl SeedChain {
+  /// Creates a chain with enough links for the given number of placements.
+  pub fn new<R: Rng>(placements: usize, rng: &mut R) -> SeedChain {
+    let mut secret = [0; 32];
+    rng.fill_bytes(&mut secret);
+    SeedChain::from_secret(secret, placements)
+  }
+
+  /// Creates the chain that grows from a secret, the last link of the chain. The same secret always
+  /// makes the same chain, so only the secret needs to be kept.
+  pub fn from_secret(secret: Seed, placements: usize) -> SeedChain {
+    let mut links = vec![secret];
+    for _ in 0..placements {
+      let next = sha256(&[links.last().unwrap()]);
+      links.push(next);
+    }
+    links.reverse();
+    SeedChain {
+      links: links
+    }
+  }
+
+  /// The link to send before the match starts.
+  pub fn commitment(&self) -> Seed {
+    self.links[0]
+  }
+
+  /// The seed for a placement, starting at 1.
+  pub fn reveal(&self, placement: usize) -> Option<Seed> {
+    if placement == 0 {
+      return None;
+    }
+    self.links.get(placement).cloned()
+  }
+
+  /// How many placements the chain has seeds for.
+  pub fn len(&self) -> usize {
+    self.links.len() - 1
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+/// Checks the seeds revealed by the other side against the link they sent first.
+#[derive(Debug, Clone)]
+pub struct ChainVerifier {
+  links: Vec<Seed>
+}
+
+impl ChainVerifier {
+  pub fn new(commitment: Seed) -> Self {
+    ChainVerifier {
+      links: vec![commitment]
+    }
+  }
+
+  /// Checks the seed for a placement, starting at 1. Seeds must be revealed in order, but the last
+  /// one may be sent again.
+  pub fn check(&mut self, placement: usize, seed: Seed) -> Result<(), String> {
+    if placement == 0 || placement > self.links.len() {
+      return Err(format!("seed {} was sent out of order", placement));
+    }
+    if placement < self.links.len() {
+      return if self.links[placement] == seed {
+        Ok(())
+      } else {
+        Err(format!("seed {} changed", placement))
+      };
+    }
+    if sha256(&[&seed]) != self.links[placement - 1] {
+      return Err(format!("seed {} does not match the seeds before it", placement));
+    }
+    self.links.push(seed);
+    Ok(())
+  }
+
+  /// The link the other side sent before the match started.
+  pub fn commitment(&self) -> Seed {
+    self.links[0]
+  }
+
+  /// The seed revealed for a placement, if it has been.
+  pub fn get(&self, placement: usize) -> Option<Seed> {
+    if placement == 0 {
+      return None;
+    }
+    self.links.get(placement).cloned()
+  }
+}
+
+/// Replays a placement with the seeds both sides revealed, and checks it came out as reported.
+///
+/// The card battled first is taken from the reports, since the player may have picked it.
+pub fn verify_placement(before: &GameState, mv: Move, host: &Seed, joiner: &Seed, battles: &[BattleReport], after: &GameState) -> bool {
+  let mut state = before.clone();
+  let first = battles.first().map(|b| b.defender);
+  match state.apply_reported(mv, first, &mut placement_rng(host, joiner)) {
+    Some(report) => report.battles == battles && state == *after,
+    None => false
+  }
+}
+
+/// Checks every roll the host of a match makes, as the host's messages arrive.
+///
+/// The joining player keeps one of these, and it also gives them the seeds they reveal.
+#[derive(Debug, Clone)]
+pub struct RollChecker {
+  own: SeedChain,
+  host: Option<ChainVerifier>,
+  /// The match before the placement being reported.
+  state: GameState,
+  /// How many placements have been made.
+  placements: usize,
+  /// The move announced for the next placement, and the card it battles first.
+  announced: Option<(Move, Option<(usize, usize)>)>,
+  placed: Option<Move>,
+  battles: Vec<BattleReport>
+}
+
+impl RollChecker {
+  pub fn new(own: SeedChain, state: GameState) -> Self {
+    RollChecker {
+      own: own,
+      host: None,
+      state: state,
+      placements: 0,
+      announced: None,
+      placed: None,
+      battles: Vec::new()
+    }
+  }
+
+  pub fn commitment(&self) -> Message {
+    Message::Commit(self.own.commitment())
+  }
+
+  /// The message revealing this side's seed for the next placement. Send it only once the move
+  /// for the placement can't change.
+  pub fn next_seed(&self) -> Option<Message> {
+    let placement = self.placements + 1;
+    self.own.reveal(placement).map(|seed| Message::Seed(placement, seed))
+  }
+
+  /// The match as of the last placement checked.
+  pub fn state(&self) -> &GameState {
+    &self.state
+  }
+
+  /// How many placements have been checked.
+  pub fn placements(&self) -> usize {
+    self.placements
+  }
+
+  /// Notes a move this side sent the host, so the host must play it as sent. Call it before
+  /// revealing the seed for the move.
+  pub fn announce(&mut self, mv: Move, first: Option<(usize, usize)>) {
+    self.announced = Some((mv, first));
+  }
+
+  /// Notes the card this side picked for its announced move to battle first.
+  pub fn announce_first(&mut self, first: (usize, usize)) {
+    if let Some((mv, _)) = self.announced {
+      self.announced = Some((mv, Some(first)));
+    }
+  }
+
+  /// Looks at a message from the host, returning why the host can't be trusted if it cheated.
+  ///
+  /// A move the host announces must say which card it battles first when it can battle more than
+  /// one, and the placement that follows must play that move and battle that card first. The same
+  /// goes for moves this side announced itself. Observe or announce a move before revealing the
+  /// seed for it.
+  pub fn observe(&mut self, message: &Message) -> Result<(), String> {
+    match *message {
+      Message::Start(_, ref state) => {
+        self.state = state.clone();
+        self.placements = 0;
+        self.announced = None;
+      },
+      Message::Move(mv, first) => {
+        let targets = self.state.battle_targets(mv);
+        if !self.state.is_legal(mv) {
+          return Err(format!("the host announced {}, which is not a legal move", format_move(&mv)));
+        }
+        match first {
+          Some(first) if !targets.contains(&first) => return Err(format!("the host announced battling {} first, which can't be battled", format_position(first))),
+          None if targets.len() > 1 => return Err("the host announced a move without the card it battles first".to_string()),
+          _ => {}
+        }
+        if let Some(announced) = self.announced {
+          if announced != (mv, first) {
+            return Err(format!("the host announced {}, not the move that was sent", format_move(&mv)));
+          }
+        }
+        self.announced = Some((mv, first));
+      },
+      Message::Commit(seed) => self.host = Some(ChainVerifier::new(seed)),
+      Message::Seed(placement, seed) => match self.host {
+        Some(ref mut host) => host.check(placement, seed)?,
+        None => return Err("the host never committed to its seeds".to_string())
+      },
+      Message::Placed(_, mv, _) => {
+        if let Some((announced, first)) = self.announced {
+          if announced != mv {
+            return Err(format!("the host announced {} but played {}", format_move(&announced), format_move(&mv)));
+          }
+          if first.is_none() && self.state.battle_targets(mv).len() > 1 {
+            return Err(format!("the host played {} before the card it battles first was picked", format_move(&mv)));
+          }
+        }
+        self.placed = Some(mv);
+        self.battles.clear();
+      },
+      Message::Battle(ref battle) => {
+        if let Some((_, Some(first))) = self.announced {
+          if self.battles.is_empty() && battle.defender != first {
+            return Err(format!("the host announced battling {} first but battled {}", format_position(first), format_position(battle.defender)));
+          }
+        }
+        self.battles.push(battle.clone());
+      },
+      Message::State(ref after) => {
+        let mv = match self.placed.take() {
+          Some(mv) => mv,
+          None => return Err("the host changed the match without a move".to_string())
+        };
+        let placement = self.placements + 1;
+        let host = self.host.as_ref().and_then(|h| h.get(placement));
+        let own = self.own.reveal(placement);
+        let fair = match (host, own) {
+          (Some(host), Some(own)) => verify_placement(&self.state, mv, &host, &own, &self.battles, after),
+          _ => return Err(format!("placement {} was rolled without both seeds", placement))
+        };
+        if !fair {
+          return Err(format!("placement {} does not match the seeds", placement));
+        }
+        self.state = after.clone();
+        self.placements = placement;
+        self.announced = None;
+      },
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use Color;
+  use fixtures::match_with_choice;
+  use net::{HostedMatch, Played};
+
+  const JOINER_SEED: u64 = 7;
+  const HOST_SEED: u64 = 9;
+
+  /// Hosts a match against the given player, committed to the joiner's seeds.
+  fn host(state: &GameState, joiner: Color, checker: &RollChecker) -> HostedMatch {
+    let mut hosted = HostedMatch::new(state.clone(), joiner, SeedChain::new(16, &mut seeded_rng(HOST_SEED)));
+    if let Message::Commit(commitment) = checker.commitment() {
+      hosted.commit(commitment).unwrap();
+    }
+    hosted
+  }
+
+  /// Has the host play a move, battling the given card first, and returns what it announces.
+  fn announce(hosted: &mut HostedMatch, mv: Move, first: Option<(usize, usize)>) -> Vec<Message> {
+    let color = hosted.state.to_move;
+    let played = match hosted.play(color, mv).unwrap() {
+      Played::Choose(_) => hosted.choose(color, first.unwrap()).unwrap(),
+      played => played
+    };
+    match played {
+      Played::Waiting(messages) => messages,
+      played => panic!("{:?} was played without the joiner's seed", played)
+    }
+  }
+
+  fn reveal(hosted: &mut HostedMatch, checker: &RollChecker) -> Vec<Message> {
+    match checker.next_seed() {
+      Some(Message::Seed(placement, seed)) => match hosted.reveal(placement, seed).unwrap() {
+        Played::Done(messages) => messages,
+        played => panic!("{:?} is still waiting", played)
+      },
+      _ => panic!("The joiner ran out of seeds")
+    }
+  }
+
+  fn observe_all(checker: &mut RollChecker, messages: &[Message]) -> Result<(), String> {
+    messages.iter().map(|m| checker.observe(m)).collect()
+  }
+
+  #[test]
+  fn honest_host_passes() {
+    let (state, mv) = match_with_choice(1).unwrap();
+    let mut checker = RollChecker::new(SeedChain::new(16, &mut seeded_rng(JOINER_SEED)), state.clone());
+    let mut hosted = host(&state, state.to_move.opponent(), &checker);
+    checker.observe(&hosted.commitment()).unwrap();
+    let first = state.battle_targets(mv)[1];
+    let announced = announce(&mut hosted, mv, Some(first));
+    assert_eq!(announced, vec![Message::Move(mv, Some(first))]);
+    observe_all(&mut checker, &announced).unwrap();
+    let rolled = reveal(&mut hosted, &checker);
+    observe_all(&mut checker, &rolled).unwrap();
+    assert_eq!(checker.placements, 1);
+    assert_eq!(checker.state, hosted.state);
+  }
+
+  #[test]
+  fn host_playing_another_move_is_caught() {
+    let (state, mv) = match_with_choice(2).unwrap();
+    let mut checker = RollChecker::new(SeedChain::new(16, &mut seeded_rng(JOINER_SEED)), state.clone());
+    let first = state.battle_targets(mv)[0];
+    let mut honest = host(&state, state.to_move.opponent(), &checker);
+    checker.observe(&honest.commitment()).unwrap();
+    observe_all(&mut checker, &announce(&mut honest, mv, Some(first))).unwrap();
+    // Once the joiner's seed is out, the host plays something else instead.
+    let other = state.legal_moves().into_iter().find(|&m| m != mv && state.battle_targets(m).len() < 2).unwrap();
+    let mut cheat = host(&state, state.to_move.opponent(), &checker);
+    announce(&mut cheat, other, None);
+    let rolled = reveal(&mut cheat, &checker);
+    assert!(observe_all(&mut checker, &rolled).is_err());
+  }
+
+  #[test]
+  fn host_battling_another_card_first_is_caught() {
+    let (state, mv) = match_with_choice(4).unwrap();
+    let targets = state.battle_targets(mv);
+    let mut checker = RollChecker::new(SeedChain::new(16, &mut seeded_rng(JOINER_SEED)), state.clone());
+    let mut honest = host(&state, state.to_move.opponent(), &checker);
+    checker.observe(&honest.commitment()).unwrap();
+    observe_all(&mut checker, &announce(&mut honest, mv, Some(targets[0]))).unwrap();
+    let mut cheat = host(&state, state.to_move.opponent(), &checker);
+    announce(&mut cheat, mv, Some(targets[1]));
+    let rolled = reveal(&mut cheat, &checker);
+    assert!(observe_all(&mut checker, &rolled).is_err());
+  }
+
+  #[test]
+  fn host_changing_the_joiners_move_is_caught() {
+    let (state, mv) = match_with_choice(5).unwrap();
+    let targets = state.battle_targets(mv);
+    let other = state.legal_moves().into_iter().find(|&m| m != mv && state.battle_targets(m).len() < 2).unwrap();
+    // The host plays the move as sent, plays another one, or battles another card first.
+    for &(played, first, honest) in &[(mv, Some(targets[0]), true), (other, None, false), (mv, Some(targets[1]), false)] {
+      let mut checker = RollChecker::new(SeedChain::new(16, &mut seeded_rng(JOINER_SEED)), state.clone());
+      let mut hosted = host(&state, state.to_move, &checker);
+      checker.observe(&hosted.commitment()).unwrap();
+      checker.announce(mv, None);
+      checker.announce_first(targets[0]);
+      assert!(announce(&mut hosted, played, first).is_empty());
+      let rolled = reveal(&mut hosted, &checker);
+      assert_eq!(observe_all(&mut checker, &rolled).is_ok(), honest);
+    }
+  }
+
+  #[test]
+  fn host_playing_the_joiners_move_before_the_pick_is_caught() {
+    let (state, mv) = match_with_choice(6).unwrap();
+    let targets = state.battle_targets(mv);
+    let mut checker = RollChecker::new(SeedChain::new(16, &mut seeded_rng(JOINER_SEED)), state.clone());
+    let mut hosted = host(&state, state.to_move, &checker);
+    checker.observe(&hosted.commitment()).unwrap();
+    checker.announce(mv, None);
+    announce(&mut hosted, mv, Some(targets[1]));
+    let rolled = reveal(&mut hosted, &checker);
+    assert!(observe_all(&mut checker, &rolled).is_err());
+  }
+
+  #[test]
+  fn announcing_without_a_pick_is_caught() {
+    let (state, mv) = match_with_choice(3).unwrap();
+    let mut checker = RollChecker::new(SeedChain::new(16, &mut seeded_rng(JOINER_SEED)), state);
+    assert!(checker.observe(&Message::Move(mv, None)).is_err());
+  }
+}
@@ -0,0 +1,49 @@
+use {seeded_rng, Card, Color, OwnedCard};
+use generator::BoardGenerator;
+use state::{GameState, Move};
+
+use rand::Rng;
+
+/// Makes a hand of random cards.
+pub fn hand<R: Rng>(rng: &mut R, size: usize) -> Vec<Card> {
+  (0..size).map(|_| Card::random(rng)).collect()
+}
+
+/// A match on the board generated from `seed`, with five random cards each and blue to move.
+pub fn new_match(seed: u64) -> GameState {
+  let mut rng = seeded_rng(seed);
+  let board = BoardGenerator::new().seed(seed).generate();
+  let blue = hand(&mut rng, 5);
+  let red = hand(&mut rng, 5);
+  GameState::new(board, blue, red, Color::Blue)
+}
+
+/// A new match with cards of both colors on about half of the free spaces, placed without
+/// battles.
+pub fn crowded_match(seed: u64) -> GameState {
+  let mut rng = seeded_rng(seed + 1000);
+  let mut state = new_match(seed);
+  for (row, column) in state.empty_spaces() {
+    if rng.gen() {
+      let color = if rng.gen() { Color::Red } else { Color::Blue };
+      state.board.add_card(row, column, OwnedCard::new(Card::random(&mut rng), color));
+    }
+  }
+  state
+}
+
+/// A match a few random placements in, where the player to move has a move that can battle more
+/// than one card.
+pub fn match_with_choice(seed: u64) -> Option<(GameState, Move)> {
+  let mut rng = seeded_rng(seed);
+  let mut state = new_match(seed);
+  while !state.is_over() {
+    let moves = state.legal_moves();
+    if let Some(&mv) = moves.iter().find(|&&mv| state.battle_targets(mv).len() > 1) {
+      return Some((state, mv));
+    }
+    let mv = moves[rng.gen_range(0, moves.len())];
+    state.apply_with_rng(mv, &mut rng);
+  }
+  None
+}
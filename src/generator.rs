@@ -0,0 +1,223 @@
+use {Board, Space, DEFAULT_SIZE, seeded_rng};
+
+use rand::{thread_rng, Rng};
+
+/// How blocks are placed on a generated board.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Blocks {
+  /// No blocks at all.
+  None,
+  /// Each space becomes a block with the given probability, until the maximum is reached.
+  Chance(f64),
+  /// Between the given minimum and maximum number of blocks, inclusive.
+  ///
+  /// With a mirror, some counts are not possible, in which case the next smaller count is used.
+  Count(usize, usize),
+  /// Blocks exactly at the given positions.
+  Fixed(Vec<(usize, usize)>)
+}
+
+/// The symmetry of generated block layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+  None,
+  /// The left half of the board mirrors the right half.
+  Horizontal,
+  /// The top half of the board mirrors the bottom half.
+  Vertical,
+  /// The board looks the same when turned upside down.
+  Rotational
+}
+
+impl Mirror {
+  /// Finds the space mirroring the given space, with both positions starting at 0.
+  fn image(&self, row: usize, col: usize, width: usize, height: usize) -> (usize, usize) {
+    match *self {
+      Mirror::None => (row, col),
+      Mirror::Horizontal => (row, width - 1 - col),
+      Mirror::Vertical => (height - 1 - row, col),
+      Mirror::Rotational => (height - 1 - row, width - 1 - col)
+    }
+  }
+}
+
+/// Generates boards, placing blocks according to its settings.
+///
+/// By default, this generates a standard board where each space has a one in four chance of being
+/// a block, with no more than six blocks.
+///
+/// Generators with a seed always generate the same board.
+#[derive(Debug, Clone)]
+pub struct BoardGenerator {
+  width: usize,
+  height: usize,
+  blocks: Blocks,
+  max_blocks: Option<usize>,
+  mirror: Mirror,
+  seed: Option<u64>
+}
+
+impl Default for BoardGenerator {
+  fn default() -> Self {
+    BoardGenerator {
+      width: DEFAULT_SIZE,
+      height: DEFAULT_SIZE,
+      blocks: Blocks::Chance(0.25),
+      max_blocks: None,
+      mirror: Mirror::None,
+      seed: None
+    }
+  }
+}
+
+impl BoardGenerator {
+  pub fn new() -> Self {
+    BoardGenerator::default()
+  }
+
+  pub fn size(mut self, width: usize, height: usize) -> Self {
+    self.width = width;
+    self.height = height;
+    self
+  }
+
+  pub fn blocks(mut self, blocks: Blocks) -> Self {
+    self.blocks = blocks;
+    self
+  }
+
+  pub fn no_blocks(self) -> Self {
+    self.blocks(Blocks::None)
+  }
+
+  pub fn chance(self, probability: f64) -> Self {
+    self.blocks(Blocks::Chance(probability))
+  }
+
+  pub fn exactly(self, blocks: usize) -> Self {
+    self.blocks(Blocks::Count(blocks, blocks))
+  }
+
+  pub fn between(self, min: usize, max: usize) -> Self {
+    self.blocks(Blocks::Count(min, max))
+  }
+
+  /// Places blocks at the given positions, starting at 1. Mirroring is not applied to these.
+  pub fn fixed(self, positions: Vec<(usize, usize)>) -> Self {
+    self.blocks(Blocks::Fixed(positions))
+  }
+
+  /// Sets the most blocks a `Chance` layout may have. This defaults to `Board::max_blocks`.
+  pub fn max_blocks(mut self, max: usize) -> Self {
+    self.max_blocks = Some(max);
+    self
+  }
+
+  pub fn mirror(mut self, mirror: Mirror) -> Self {
+    self.mirror = mirror;
+    self
+  }
+
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.seed = Some(seed);
+    self
+  }
+
+  pub fn generate(&self) -> Board {
+    let seed = match self.seed {
+      Some(s) => s,
+      None => thread_rng().gen()
+    };
+    self.generate_with_rng(&mut seeded_rng(seed))
+  }
+
+  /// Generates a board using the given random number generator, ignoring the seed.
+  pub fn generate_with_rng<R: Rng>(&self, rng: &mut R) -> Board {
+    let mut board = Board::new(self.width, self.height);
+    let blocks = match self.blocks {
+      Blocks::None => Vec::new(),
+      Blocks::Fixed(ref positions) => {
+        for &(row, col) in positions {
+          assert!(row >= 1 && col >= 1 && row <= self.height && col <= self.width, "Block outside of board");
+        }
+        positions.iter().map(|&(row, col)| (row - 1, col - 1)).collect()
+      },
+      Blocks::Chance(probability) => {
+        let max = self.max_blocks.unwrap_or_else(|| Board::max_blocks(self.width, self.height));
+        let mut blocks = Vec::new();
+        for group in self.groups() {
+          if blocks.len() + group.len() <= max && rng.next_f64() < probability {
+            blocks.extend(group);
+          }
+        }
+        blocks
+      },
+      Blocks::Count(min, max) => {
+        let spaces = self.width * self.height;
+        // A minimum above the maximum is read as the same range the other way around.
+        let (min, max) = if min > max { (max, min) } else { (min, max) };
+        let count = rng.gen_range(min.min(spaces), max.min(spaces) + 1);
+        let mut groups = self.groups();
+        rng.shuffle(&mut groups);
+        let mut blocks = Vec::new();
+        for group in groups {
+          if blocks.len() + group.len() <= count {
+            blocks.extend(group);
+          }
+        }
+        blocks
+      }
+    };
+    for (row, col) in blocks {
+      board.spaces[row][col] = Space::Block;
+    }
+    board
+  }
+
+  /// Groups spaces that mirror each other, so they are made blocks together.
+  ///
+  /// Spaces are in row order, starting at 0.
+  fn groups(&self) -> Vec<Vec<(usize, usize)>> {
+    let mut seen = vec![vec![false; self.width]; self.height];
+    let mut groups = Vec::new();
+    for row in 0..self.height {
+      for col in 0..self.width {
+        if seen[row][col] {
+          continue;
+        }
+        let mut group = vec![(row, col)];
+        let (r, c) = self.mirror.image(row, col, self.width, self.height);
+        if (r, c) != (row, col) {
+          group.push((r, c));
+        }
+        for &(r, c) in &group {
+          seen[r][c] = true;
+        }
+        groups.push(group);
+      }
+    }
+    groups
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn same_seed_same_layout() {
+    for seed in 0..50 {
+      let generator = BoardGenerator::new().size(5, 4).between(1, 6).mirror(Mirror::Horizontal).seed(seed);
+      assert_eq!(format!("{:?}", generator.generate()), format!("{:?}", generator.generate()));
+    }
+  }
+
+  #[test]
+  fn reversed_count_is_a_range() {
+    for seed in 0..50 {
+      let board = BoardGenerator::new().between(5, 2).seed(seed).generate();
+      let blocks = board.spaces.iter().flat_map(|r| r.iter()).filter(|s| s.is_block()).count();
+      assert!(blocks >= 2 && blocks <= 5);
+    }
+  }
+}
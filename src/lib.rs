@@ -1,10 +1,34 @@
 extern crate rand;
-
-use rand::{thread_rng, Rng};
+extern crate sha2;
+
+pub mod ai;
+pub mod bot;
+pub mod bitboard;
+pub mod collection;
+pub mod collector;
+pub mod correspondence;
+pub mod fair;
+pub mod generator;
+pub mod lobby;
+pub mod net;
+pub mod notation;
+pub mod preview;
+pub mod report;
+pub mod score;
+pub mod state;
+pub mod symmetry;
+pub mod transposition;
+pub mod zobrist;
+
+#[cfg(test)]
+mod fixtures;
+
+use rand::{thread_rng, Rng, SeedableRng, XorShiftRng};
+
+use generator::BoardGenerator;
 
 use std::cmp::{min, max};
-use std::mem::{self, uninitialized};
-use std::cell::Cell;
+use std::mem;
 
 const STAT_RANGES: &'static [[u8; 2]] = &[
   [0, 15],
@@ -26,11 +50,20 @@ const STAT_RANGES: &'static [[u8; 2]] = &[
 ];
 
 fn stat(level: u8) -> Option<u8> {
+  stat_with_rng(level, &mut thread_rng())
+}
+
+fn stat_with_rng<R: Rng>(level: u8, rng: &mut R) -> Option<u8> {
   if level > 0x0F {
     return None;
   }
   let range = STAT_RANGES[level as usize];
-  Some(thread_rng().gen_range(range[0] as u16, range[1] as u16 + 1) as u8)
+  Some(rng.gen_range(range[0] as u16, range[1] as u16 + 1) as u8)
+}
+
+/// Creates a random number generator that always produces the same numbers for the same seed.
+pub fn seeded_rng(seed: u64) -> XorShiftRng {
+  XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x9E37_79B9, 0x7F4A_7C15])
 }
 
 pub struct TetraMaster;
@@ -95,18 +128,23 @@ impl TetraMaster {
   }
 
   pub fn battle(attacker: &Card, defender: &Card) -> BattleResult {
-    let attacker_power = stat(attacker.offense_level()).expect("Invalid card");
-    let defender_defense = stat(attacker.defense_level(defender)).expect("Invalid card");
-    let attack_score = thread_rng().gen_range(0, attacker_power as u16 + 1) as u8;
-    let defense_score = thread_rng().gen_range(0, defender_defense as u16 + 1) as u8;
-    let final_attack = attacker_power - attack_score;
-    let final_defense = defender_defense - defense_score;
-    if final_attack == final_defense {
-      BattleResult::Draw
-    } else if final_attack > final_defense {
-      BattleResult::Attacker
-    } else {
-      BattleResult::Defender
+    TetraMaster::battle_with_rng(attacker, defender, &mut thread_rng())
+  }
+
+  /// Battles two cards, rolling with the given random number generator.
+  pub fn battle_with_rng<R: Rng>(attacker: &Card, defender: &Card, rng: &mut R) -> BattleResult {
+    TetraMaster::roll_with_rng(attacker, defender, rng).result()
+  }
+
+  /// Rolls the final scores of a battle between two cards.
+  pub fn roll_with_rng<R: Rng>(attacker: &Card, defender: &Card, rng: &mut R) -> Roll {
+    let attacker_power = stat_with_rng(attacker.offense_level(), rng).expect("Invalid card");
+    let defender_defense = stat_with_rng(attacker.defense_level(defender), rng).expect("Invalid card");
+    let attack_score = rng.gen_range(0, attacker_power as u16 + 1) as u8;
+    let defense_score = rng.gen_range(0, defender_defense as u16 + 1) as u8;
+    Roll {
+      attack: attacker_power - attack_score,
+      defense: defender_defense - defense_score
     }
   }
 
@@ -138,7 +176,7 @@ impl TetraMaster {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Space {
   Block,
   Card(PlacedCard),
@@ -178,34 +216,56 @@ impl ToString for Space {
   }
 }
 
-#[derive(Debug)]
+/// The width and height of a standard board.
+pub const DEFAULT_SIZE: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Board {
-  pub spaces: [[Space; 4]; 4]
+  /// The spaces of the board, by row and then by column.
+  pub spaces: Vec<Vec<Space>>
 }
 
 impl Board {
-  pub fn generate() -> Self {
-    let mut slice: [[Space; 4]; 4] = unsafe { [uninitialized(), uninitialized(), uninitialized(), uninitialized()] };
-    let mut blocks = 0;
-    for mut spaces in &mut slice {
-      *spaces = Board::generate_row(&mut blocks);
-    }
+  /// Creates a board of the given size with no blocks.
+  pub fn new(width: usize, height: usize) -> Self {
+    assert!(width > 0 && height > 0, "Board must have at least one space");
     Board {
-      spaces: slice
+      spaces: (0..height).map(|_| (0..width).map(|_| Space::Empty).collect()).collect()
     }
   }
 
-  fn generate_row(blocks: &mut u8) -> [Space; 4] {
-    let mut slice: [Space; 4] = unsafe { [uninitialized(), uninitialized(), uninitialized(), uninitialized()] };
-    for mut space in &mut slice {
-      *space = if *blocks < 6 && thread_rng().gen_weighted_bool(4) {
-        *blocks += 1;
-        Space::Block
-      } else {
-        Space::Empty
-      };
-    }
-    slice
+  pub fn generate() -> Self {
+    Board::generate_sized(DEFAULT_SIZE, DEFAULT_SIZE)
+  }
+
+  /// Generates a board of the given size, with blocks placed as on a standard board.
+  ///
+  /// See `generator::BoardGenerator` for more control over where blocks go.
+  pub fn generate_sized(width: usize, height: usize) -> Self {
+    BoardGenerator::new().size(width, height).generate()
+  }
+
+  /// The most blocks a generated board of the given size will have.
+  ///
+  /// This is six on a standard board, and scales with the number of spaces.
+  pub fn max_blocks(width: usize, height: usize) -> usize {
+    width * height * 3 / 8
+  }
+
+  /// How many cards each player should have for a board of the given size.
+  ///
+  /// This is five on a standard board. There is always room for both hands, however many blocks
+  /// are generated.
+  pub fn hand_size(width: usize, height: usize) -> usize {
+    (width * height - Board::max_blocks(width, height)) / 2
+  }
+
+  pub fn width(&self) -> usize {
+    self.spaces[0].len()
+  }
+
+  pub fn height(&self) -> usize {
+    self.spaces.len()
   }
 
   pub fn add_card(&mut self, row: usize, column: usize, card: OwnedCard) -> &PlacedCard {
@@ -259,7 +319,7 @@ impl Board {
         if *r == row && *c == column {
           continue;
         }
-        if *r > 4 || *c > 4 || *r < 1 || *c < 1 {
+        if *r > self.height() || *c > self.width() || *r < 1 || *c < 1 {
           cards.push(None);
           continue;
         }
@@ -273,81 +333,112 @@ impl Board {
     cards
   }
 
-  fn do_combo(&self, winner: &PlacedCard, loser: &PlacedCard) {
-    let combos: Vec<&PlacedCard> = self.neighbors_pos(loser.row, loser.column)
-      .into_iter()
-      .enumerate()
-      .filter(|&(_, x)| x.is_some())
-      .map(|(i, x)| (i, x.unwrap()))
-      .filter(|&(_, x)| x.color.get() != winner.color.get())
-      .map(|(i, x)| (loser.arrows.relation_from(i.into(), &x.arrows), x))
-      .filter(|&(ref r, _)| *r != ArrowRelation::Ignore)
-      .map(|(_, x)| x)
-      .collect();
-    for combo in combos {
-      combo.color.set(winner.color.get());
+  /// Gets the card at the given location, if there is one.
+  pub fn card(&self, row: usize, column: usize) -> Option<&PlacedCard> {
+    match *self.space(row, column) {
+      Space::Card(ref c) => Some(c),
+      _ => None
+    }
+  }
+
+  fn set_color(&mut self, row: usize, column: usize, color: Color) {
+    if let Space::Card(ref mut c) = *self.space_mut(row, column) {
+      c.card.color = color;
+    }
+  }
+
+  /// Places a card on a copy of this board and runs its battles, leaving this board untouched.
+  pub fn place(&self, row: usize, column: usize, card: OwnedCard) -> Board {
+    let mut board = self.clone();
+    board.add_card(row, column, card);
+    board.run_battles_pos(row, column);
+    board
+  }
+
+  /// Flips the cards the loser of a battle points at to the winner, returning where they are.
+  fn do_combo(&mut self, winner: Color, row: usize, col: usize) -> Vec<(usize, usize)> {
+    let combos: Vec<(usize, usize)> = {
+      let loser = match self.card(row, col) {
+        Some(c) => c,
+        None => return Vec::new()
+      };
+      self.neighbors_pos(row, col)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, x)| x.is_some())
+        .map(|(i, x)| (i, x.unwrap()))
+        .filter(|&(_, x)| x.color != winner)
+        .map(|(i, x)| (loser.arrows.relation_from(i.into(), &x.arrows), x))
+        .filter(|&(ref r, _)| *r != ArrowRelation::Ignore)
+        .map(|(_, x)| (x.row, x.column))
+        .collect()
+    };
+    for &(r, c) in &combos {
+      self.set_color(r, c, winner);
     }
+    combos
   }
 
-  pub fn run_battles(&self, card: &PlacedCard) {
+  pub fn run_battles(&mut self, card: &PlacedCard) {
     self.run_battles_pos(card.row, card.column)
   }
 
-  pub fn run_battles_pos(&self, row: usize, col: usize) {
-    let card = match *self.space(row, col) {
-      Space::Card(ref c) => c,
-      _ => return
+  pub fn run_battles_pos(&mut self, row: usize, col: usize) {
+    self.run_battles_pos_with_rng(row, col, &mut thread_rng())
+  }
+
+  /// Finds the color of the card at the given location and how it relates to each opposing card
+  /// next to it, in the order battles are run.
+  fn relations(&self, row: usize, col: usize) -> Option<(Color, Vec<(ArrowRelation, (usize, usize))>)> {
+    let card = match self.card(row, col) {
+      Some(c) => c,
+      None => return None
     };
-    let relations: Vec<(ArrowRelation, &PlacedCard)> = self.neighbors_pos(row, col)
+    let relations = self.neighbors_pos(row, col)
       .into_iter()
       .enumerate()
       .filter(|&(_, x)| x.is_some())
       .map(|(i, x)| (i, x.unwrap()))
-      .filter(|&(_, x)| x.color.get() != card.color.get())
-      .map(|(i, x)| (card.arrows.relation_from(i.into(), &x.arrows), x))
+      .filter(|&(_, x)| x.color != card.color)
+      .map(|(i, x)| (card.arrows.relation_from(i.into(), &x.arrows), (x.row, x.column)))
       .collect();
-    let battles: Vec<&PlacedCard> = relations.iter()
-      .filter(|&&(ref rel, _)| *rel == ArrowRelation::Battle)
-      .map(|&(_, c)| c)
-      .collect();
-    let mut lost_any = false;
-    for defender in &battles {
-      match TetraMaster::battle(card, defender) {
-        BattleResult::Attacker => {
-          defender.color.set(card.color.get());
-          self.do_combo(card, defender);
-        },
-        BattleResult::Defender => {
-          card.color.set(defender.color.get());
-          self.do_combo(defender, card);
-          lost_any = true;
-          break;
-        },
-        BattleResult::Draw => {
-          self.run_battles_pos(row, col);
-          return;
-        }
-      }
-    }
-    if !lost_any {
-      let takes = relations.iter()
-        .filter(|&&(ref rel, _)| *rel == ArrowRelation::Take)
-        .map(|&(_, c)| c);
-      for take in takes {
-        take.color.set(card.color.get());
-      }
-    }
+    Some((card.color, relations))
+  }
+
+  /// Runs the battles for the card at the given location, rolling with the given random number
+  /// generator.
+  pub fn run_battles_pos_with_rng<R: Rng>(&mut self, row: usize, col: usize, rng: &mut R) {
+    self.run_battles_reported(row, col, None, rng);
   }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BattleResult {
   Attacker,
   Defender,
   Draw
 }
 
-#[derive(Debug)]
+/// The final scores of a battle.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Roll {
+  pub attack: u8,
+  pub defense: u8
+}
+
+impl Roll {
+  pub fn result(&self) -> BattleResult {
+    if self.attack == self.defense {
+      BattleResult::Draw
+    } else if self.attack > self.defense {
+      BattleResult::Attacker
+    } else {
+      BattleResult::Defender
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Card {
   pub power: u8,
   pub class: Class,
@@ -373,6 +464,17 @@ impl Card {
     card
   }
 
+  /// Makes a card with every stat, class and arrow equally likely.
+  pub fn random<R: Rng>(rng: &mut R) -> Self {
+    let class = match rng.gen_range(0, 4) {
+      0 => Class::Physical,
+      1 => Class::Magical,
+      2 => Class::Flexible,
+      _ => Class::Assault
+    };
+    Card::with_arrows(rng.gen_range(0, 16), class, rng.gen_range(0, 16), rng.gen_range(0, 16), Arrows::from_flags(rng.gen()))
+  }
+
   /// Gets this card's offense level.
   pub fn offense_level(&self) -> u8 {
     match self.class {
@@ -437,7 +539,7 @@ pub enum ArrowRelation {
   Battle
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Arrows {
   pub flags: u8
 }
@@ -566,7 +668,7 @@ impl Arrows {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Class {
   Physical,
   Magical,
@@ -585,10 +687,12 @@ impl Class {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OwnedCard {
   pub card: Card,
-  pub color: Cell<Color>
+  pub color: Color,
+  /// The color of the player who brought this card to the match.
+  pub original_color: Color
 }
 
 impl std::ops::Deref for OwnedCard {
@@ -603,7 +707,8 @@ impl OwnedCard {
   pub fn new(card: Card, color: Color) -> Self {
     OwnedCard {
       card: card,
-      color: Cell::new(color)
+      color: color,
+      original_color: color
     }
   }
 
@@ -615,12 +720,17 @@ impl OwnedCard {
     Self::new(card, Color::Red)
   }
 
+  /// Whether this card has been taken from the player who brought it.
+  pub fn is_flipped(&self) -> bool {
+    self.color != self.original_color
+  }
+
   pub fn into_inner(self) -> Card {
     self.card
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PlacedCard {
   pub card: OwnedCard,
   pub row: usize,
@@ -650,3 +760,12 @@ pub enum Color {
   Blue,
   Red
 }
+
+impl Color {
+  pub fn opponent(&self) -> Color {
+    match *self {
+      Color::Blue => Color::Red,
+      Color::Red => Color::Blue
+    }
+  }
+}
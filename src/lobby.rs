@@ -0,0 +1,427 @@
+use {Board, Card, Color};
+use generator::BoardGenerator;
+use net::{new_token, report_messages, Message, Rules, PROTOCOL_VERSION, RECONNECT_GRACE};
+use notation::{format_move, format_position};
+use state::{GameState, Move};
+
+use rand::Rng;
+
+use std::time::{Duration, Instant};
+
+/// The widest or tallest board a table can be opened with.
+pub const MAX_SIZE: usize = 8;
+
+/// Tells the lobby apart the players connected to it.
+pub type PlayerId = usize;
+
+/// A table in a lobby, open until a second player sits at it.
+#[derive(Debug, Clone)]
+struct Table {
+  id: usize,
+  rules: Rules,
+  /// The players at the table. The player who opened it plays blue.
+  players: Vec<PlayerId>,
+  /// The cards each player brings, once they have sent them.
+  hands: [Option<Vec<Card>>; 2],
+  /// The match being played, once both players have sent their cards.
+  state: Option<GameState>,
+  /// A move waiting for its player to pick which card to battle first.
+  pending: Option<Move>,
+  /// The tokens each player can come back to the match with.
+  tokens: Vec<String>,
+  /// A player whose connection was lost during the match, and when.
+  away: Option<(PlayerId, Instant)>
+}
+
+impl Table {
+  fn color(&self, player: PlayerId) -> Color {
+    if self.players[0] == player {
+      Color::Blue
+    } else {
+      Color::Red
+    }
+  }
+}
+
+/// Whether a table can be opened with the given rules.
+pub fn rules_allowed(rules: &Rules) -> bool {
+  rules.width <= MAX_SIZE
+    && rules.height <= MAX_SIZE
+    && rules.hand_size > 0
+    && rules.hand_size <= Board::hand_size(rules.width, rules.height)
+}
+
+/// The tables of a server and the matches played at them.
+///
+/// Players open tables with the rules they want, and others sit at them. The lobby plays every
+/// match itself, rolling every battle and checking every move, so the players only ever send what
+/// they want to do.
+///
+/// The lobby doesn't know about connections. Each message is handled for a player, and the
+/// messages to send back are returned along with who to send them to.
+pub struct Lobby<R: Rng> {
+  rng: R,
+  next_id: usize,
+  tables: Vec<Table>
+}
+
+impl<R: Rng> Lobby<R> {
+  pub fn new(rng: R) -> Self {
+    Lobby {
+      rng: rng,
+      next_id: 1,
+      tables: Vec::new()
+    }
+  }
+
+  /// The tables waiting for a second player, and their rules.
+  pub fn open_tables(&self) -> Vec<(usize, Rules)> {
+    self.tables
+      .iter()
+      .filter(|t| t.players.len() == 1)
+      .map(|t| (t.id, t.rules))
+      .collect()
+  }
+
+  fn table_of(&self, player: PlayerId) -> Option<usize> {
+    self.tables.iter().position(|t| t.players.contains(&player))
+  }
+
+  /// Handles a message from a player.
+  pub fn handle(&mut self, player: PlayerId, message: Message) -> Vec<(PlayerId, Message)> {
+    let table = self.table_of(player);
+    let result = match (table, message) {
+      (_, Message::List) => Ok(vec![(player, Message::Tables(self.open_tables()))]),
+      (_, Message::Bye) => Ok(self.leave(player)),
+      (None, Message::Resume(version, token, _)) => self.resume(player, version, &token),
+      (None, Message::Create(version, rules)) => self.create(player, version, rules),
+      (None, Message::Sit(id)) => self.sit(player, id),
+      (Some(_), Message::Create(..)) | (Some(_), Message::Sit(_)) | (Some(_), Message::Resume(..)) => Err("you are already at a table".to_string()),
+      (Some(t), Message::Join(version, hand)) => self.join(t, player, version, hand),
+      (Some(t), Message::Move(mv, _)) => self.play(t, player, mv),
+      (Some(t), Message::First(first)) => self.choose(t, player, first),
+      (None, _) => Err("sit at a table first".to_string()),
+      (Some(_), _) => Err("that can't be sent during a match".to_string())
+    };
+    match result {
+      Ok(messages) => messages,
+      Err(reason) => vec![(player, Message::Illegal(reason))]
+    }
+  }
+
+  /// Takes a player out of the lobby, closing their table. The other player at it is told.
+  pub fn leave(&mut self, player: PlayerId) -> Vec<(PlayerId, Message)> {
+    match self.table_of(player) {
+      Some(t) => self.tables
+        .remove(t)
+        .players
+        .into_iter()
+        .filter(|&p| p != player)
+        .map(|p| (p, Message::Left))
+        .collect(),
+      None => Vec::new()
+    }
+  }
+
+  /// Notes that a player's connection was lost. A match they were playing waits for them to come
+  /// back with their token, and the other player is told. Otherwise they leave.
+  pub fn disconnect(&mut self, player: PlayerId, now: Instant) -> Vec<(PlayerId, Message)> {
+    let t = match self.table_of(player) {
+      Some(t) => t,
+      None => return Vec::new()
+    };
+    let playing = self.tables[t].state.as_ref().map(|s| !s.is_over()).unwrap_or(false);
+    if !playing || self.tables[t].away.is_some() {
+      return self.leave(player);
+    }
+    let table = &mut self.tables[t];
+    table.away = Some((player, now));
+    table.players.iter().filter(|&&p| p != player).map(|&p| (p, Message::Away)).collect()
+  }
+
+  /// Closes the tables of players who didn't come back in time.
+  pub fn expire(&mut self, now: Instant) -> Vec<(PlayerId, Message)> {
+    let grace = Duration::from_secs(RECONNECT_GRACE);
+    let expired: Vec<PlayerId> = self.tables
+      .iter()
+      .filter_map(|t| t.away)
+      .filter(|&(_, since)| now.duration_since(since) >= grace)
+      .map(|(player, _)| player)
+      .collect();
+    expired.into_iter().flat_map(|p| self.leave(p)).collect()
+  }
+
+  /// Puts a player who came back with their token back in their match, sending them everything
+  /// they need to carry on.
+  fn resume(&mut self, player: PlayerId, version: u32, token: &str) -> Result<Vec<(PlayerId, Message)>, String> {
+    if version != PROTOCOL_VERSION {
+      return Err(format!("version {} is needed", PROTOCOL_VERSION));
+    }
+    let found = self.tables.iter().position(|t| match t.away {
+      Some((away, _)) => t.players.iter().position(|&p| p == away).map(|i| t.tokens[i] == token).unwrap_or(false),
+      None => false
+    });
+    let table = match found {
+      Some(t) => &mut self.tables[t],
+      None => return Err("there is no match to come back to".to_string())
+    };
+    let (away, _) = table.away.take().expect("Table was found with a player away");
+    for p in &mut table.players {
+      if *p == away {
+        *p = player;
+      }
+    }
+    let color = table.color(player);
+    let state = table.state.clone().expect("Match was checked");
+    let mut messages = vec![(player, Message::Start(color, state.clone()))];
+    if let Some(mv) = table.pending {
+      if state.to_move == color {
+        messages.push((player, Message::Choose(state.battle_targets(mv))));
+      }
+    }
+    Ok(messages)
+  }
+
+  fn create(&mut self, player: PlayerId, version: u32, rules: Rules) -> Result<Vec<(PlayerId, Message)>, String> {
+    if version != PROTOCOL_VERSION {
+      return Err(format!("version {} is needed", PROTOCOL_VERSION));
+    }
+    if !rules_allowed(&rules) {
+      return Err("those rules aren't allowed".to_string());
+    }
+    let id = self.next_id;
+    self.next_id += 1;
+    self.tables.push(Table {
+      id: id,
+      rules: rules,
+      players: vec![player],
+      hands: [None, None],
+      state: None,
+      pending: None,
+      tokens: Vec::new(),
+      away: None
+    });
+    Ok(vec![(player, Message::Opened(id))])
+  }
+
+  fn sit(&mut self, player: PlayerId, id: usize) -> Result<Vec<(PlayerId, Message)>, String> {
+    let table = match self.tables.iter_mut().find(|t| t.id == id && t.players.len() == 1) {
+      Some(t) => t,
+      None => return Err(format!("table {} isn't open", id))
+    };
+    table.players.push(player);
+    let hello = Message::Hello(PROTOCOL_VERSION, table.rules);
+    Ok(table.players.iter().map(|&p| (p, hello.clone())).collect())
+  }
+
+  fn join(&mut self, t: usize, player: PlayerId, version: u32, hand: Vec<Card>) -> Result<Vec<(PlayerId, Message)>, String> {
+    let seed = self.rng.gen();
+    let blue_first = self.rng.gen();
+    let table = &mut self.tables[t];
+    if version != PROTOCOL_VERSION {
+      return Err(format!("version {} is needed", PROTOCOL_VERSION));
+    }
+    if table.players.len() < 2 {
+      return Err("wait for someone to sit at your table".to_string());
+    }
+    if table.state.is_some() {
+      return Err("the match has started".to_string());
+    }
+    if hand.len() != table.rules.hand_size {
+      return Err(format!("{} cards are needed", table.rules.hand_size));
+    }
+    let i = if table.color(player) == Color::Blue { 0 } else { 1 };
+    table.hands[i] = Some(hand);
+    let (blue, red) = match (table.hands[0].clone(), table.hands[1].clone()) {
+      (Some(b), Some(r)) => (b, r),
+      _ => return Ok(Vec::new())
+    };
+    let board = BoardGenerator::new().size(table.rules.width, table.rules.height).seed(seed).generate();
+    let to_move = if blue_first { Color::Blue } else { Color::Red };
+    let state = GameState::new(board, blue, red, to_move);
+    table.state = Some(state.clone());
+    table.tokens = vec![new_token(&mut self.rng), new_token(&mut self.rng)];
+    Ok(vec![
+      (table.players[0], Message::Start(Color::Blue, state.clone())),
+      (table.players[0], Message::Token(table.tokens[0].clone())),
+      (table.players[1], Message::Start(Color::Red, state)),
+      (table.players[1], Message::Token(table.tokens[1].clone()))
+    ])
+  }
+
+  fn play(&mut self, t: usize, player: PlayerId, mv: Move) -> Result<Vec<(PlayerId, Message)>, String> {
+    let targets = {
+      let table = &self.tables[t];
+      let color = table.color(player);
+      let state = match table.state {
+        Some(ref s) => s,
+        None => return Err("the match hasn't started".to_string())
+      };
+      if state.to_move != color {
+        return Err("it is not your turn".to_string());
+      }
+      if table.pending.is_some() {
+        return Err("pick which card to battle first".to_string());
+      }
+      if !state.is_legal(mv) {
+        return Err(format!("{} is not a legal move", format_move(&mv)));
+      }
+      state.battle_targets(mv)
+    };
+    if targets.len() > 1 {
+      self.tables[t].pending = Some(mv);
+      return Ok(vec![(player, Message::Choose(targets))]);
+    }
+    Ok(self.roll(t, mv, None))
+  }
+
+  fn choose(&mut self, t: usize, player: PlayerId, first: (usize, usize)) -> Result<Vec<(PlayerId, Message)>, String> {
+    let mv = {
+      let table = &self.tables[t];
+      let mv = match (table.pending, table.state.as_ref()) {
+        (Some(mv), Some(state)) if state.to_move == table.color(player) => mv,
+        _ => return Err("there is nothing to choose".to_string())
+      };
+      if !table.state.as_ref().map(|s| s.battle_targets(mv).contains(&first)).unwrap_or(false) {
+        return Err(format!("{} can't be battled", format_position(first)));
+      }
+      mv
+    };
+    self.tables[t].pending = None;
+    Ok(self.roll(t, mv, Some(first)))
+  }
+
+  /// Plays a checked move and tells both players what happened. A match that is over closes its
+  /// table.
+  fn roll(&mut self, t: usize, mv: Move, first: Option<(usize, usize)>) -> Vec<(PlayerId, Message)> {
+    let messages = {
+      let table = &mut self.tables[t];
+      let state = table.state.as_mut().expect("Match was checked");
+      let color = state.to_move;
+      let card = state.hand(color)[mv.card];
+      let report = state.apply_reported(mv, first, &mut self.rng).expect("Move was checked");
+      report_messages(color, mv, card, &report, state)
+    };
+    let over = self.tables[t].state.as_ref().map(|s| s.is_over()).unwrap_or(false);
+    let players = if over {
+      self.tables.remove(t).players
+    } else {
+      self.tables[t].players.clone()
+    };
+    players
+      .iter()
+      .flat_map(|&p| messages.iter().cloned().map(move |m| (p, m)))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use seeded_rng;
+  use fixtures::hand;
+
+  use rand::XorShiftRng;
+
+  const BLUE: PlayerId = 1;
+  const RED: PlayerId = 2;
+
+  /// A lobby where blue opened a table, red sat at it and both sent their cards. Returns the match
+  /// and the tokens sent to blue and red.
+  fn started() -> (Lobby<XorShiftRng>, GameState, Vec<String>) {
+    let mut lobby = Lobby::new(seeded_rng(1));
+    assert_eq!(lobby.handle(BLUE, Message::Create(PROTOCOL_VERSION, Rules::default())), vec![(BLUE, Message::Opened(1))]);
+    assert_eq!(lobby.handle(RED, Message::Sit(1)).len(), 2);
+    assert!(lobby.handle(BLUE, Message::Join(PROTOCOL_VERSION, hand(&mut seeded_rng(1), 5))).is_empty());
+    let mut state = None;
+    let mut tokens = Vec::new();
+    for (player, message) in lobby.handle(RED, Message::Join(PROTOCOL_VERSION, hand(&mut seeded_rng(2), 5))) {
+      match message {
+        Message::Start(color, s) => {
+          assert_eq!(color, if player == BLUE { Color::Blue } else { Color::Red });
+          state = Some(s);
+        },
+        Message::Token(token) => tokens.push(token),
+        message => panic!("{:?} was sent when the match started", message)
+      }
+    }
+    (lobby, state.expect("Match started"), tokens)
+  }
+
+  fn player(color: Color) -> PlayerId {
+    if color == Color::Blue { BLUE } else { RED }
+  }
+
+  fn is_illegal(messages: &[(PlayerId, Message)], to: PlayerId) -> bool {
+    match messages.first() {
+      Some(&(p, Message::Illegal(_))) => messages.len() == 1 && p == to,
+      _ => false
+    }
+  }
+
+  #[test]
+  fn players_take_turns() {
+    let (mut lobby, mut state, _) = started();
+    while !state.is_over() {
+      let mover = player(state.to_move);
+      let mv = state.legal_moves()[0];
+      assert!(is_illegal(&lobby.handle(player(state.to_move.opponent()), Message::Move(mv, None)), player(state.to_move.opponent())));
+      let mut messages = lobby.handle(mover, Message::Move(mv, None));
+      let first = match messages.first() {
+        Some(&(p, Message::Choose(ref targets))) if p == mover => Some(targets[0]),
+        _ => None
+      };
+      if let Some(first) = first {
+        messages = lobby.handle(mover, Message::First(first));
+      }
+      let told: Vec<PlayerId> = messages.iter().filter(|&&(_, ref m)| match *m { Message::State(_) => true, _ => false }).map(|&(p, _)| p).collect();
+      assert_eq!(told, vec![BLUE, RED]);
+      state = match messages.last() {
+        Some(&(_, Message::State(ref s))) => s.clone(),
+        _ => panic!("The state wasn't sent last")
+      };
+    }
+    // The table closed when the match was over.
+    assert!(is_illegal(&lobby.handle(BLUE, Message::Move(Move::new(0, 1, 1), None)), BLUE));
+  }
+
+  #[test]
+  fn illegal_moves_are_refused() {
+    let (mut lobby, state, _) = started();
+    let mover = player(state.to_move);
+    assert!(is_illegal(&lobby.handle(mover, Message::Move(Move::new(9, 1, 1), None)), mover));
+    assert!(is_illegal(&lobby.handle(mover, Message::Move(Move::new(0, 9, 9), None)), mover));
+    assert!(is_illegal(&lobby.handle(mover, Message::First((1, 1))), mover));
+    assert!(is_illegal(&lobby.handle(mover, Message::Create(PROTOCOL_VERSION, Rules::default())), mover));
+    let block = (1..5).flat_map(|r| (1..5).map(move |c| (r, c))).find(|&(r, c)| state.board.space(r, c).is_block());
+    if let Some((row, column)) = block {
+      assert!(is_illegal(&lobby.handle(mover, Message::Move(Move::new(0, row, column), None)), mover));
+    }
+    // Nothing that was refused changed the match.
+    let mv = state.legal_moves()[0];
+    assert!(!is_illegal(&lobby.handle(mover, Message::Move(mv, None)), mover));
+  }
+
+  #[test]
+  fn players_can_come_back() {
+    let (mut lobby, state, tokens) = started();
+    let now = Instant::now();
+    assert_eq!(lobby.disconnect(RED, now), vec![(BLUE, Message::Away)]);
+    assert!(is_illegal(&lobby.handle(3, Message::Resume(PROTOCOL_VERSION, tokens[0].clone(), 0)), 3));
+    assert_eq!(lobby.handle(3, Message::Resume(PROTOCOL_VERSION, tokens[1].clone(), 0)), vec![(3, Message::Start(Color::Red, state.clone()))]);
+    // The player who came back plays on as red.
+    let mover = if state.to_move == Color::Blue { BLUE } else { 3 };
+    assert!(!is_illegal(&lobby.handle(mover, Message::Move(state.legal_moves()[0], None)), mover));
+    assert!(lobby.expire(now + Duration::from_secs(RECONNECT_GRACE * 2)).is_empty());
+  }
+
+  #[test]
+  fn tables_close_when_players_stay_away() {
+    let (mut lobby, _, tokens) = started();
+    let now = Instant::now();
+    lobby.disconnect(BLUE, now);
+    assert!(lobby.expire(now + Duration::from_secs(RECONNECT_GRACE - 1)).is_empty());
+    assert_eq!(lobby.expire(now + Duration::from_secs(RECONNECT_GRACE)), vec![(RED, Message::Left)]);
+    assert!(is_illegal(&lobby.handle(3, Message::Resume(PROTOCOL_VERSION, tokens[0].clone(), 0)), 3));
+  }
+}
@@ -0,0 +1,884 @@
+use {BattleResult, Board, Card, Color};
+use fair::{from_hex, placement_rng, to_hex, ChainVerifier, Seed, SeedChain};
+use notation::{format_card, format_color, format_hand, format_move, format_position, format_state};
+use notation::{parse_card, parse_color, parse_hand, parse_move, parse_position, parse_state};
+use report::{BattleReport, PlacementReport};
+use state::{GameState, Move};
+
+use rand::Rng;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+/// The version of the protocol. Peers with different versions can't play each other.
+pub const PROTOCOL_VERSION: u32 = 3;
+pub const DEFAULT_PORT: u16 = 7878;
+/// How many seconds a match waits for a player whose connection was lost to come back.
+pub const RECONNECT_GRACE: u64 = 60;
+
+/// The rules a networked match is played by, chosen by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+  pub width: usize,
+  pub height: usize,
+  pub hand_size: usize
+}
+
+impl Rules {
+  /// The rules for a board of the given size, with the usual hand size for it.
+  pub fn sized(width: usize, height: usize) -> Rules {
+    Rules {
+      width: width,
+      height: height,
+      hand_size: Board::hand_size(width, height)
+    }
+  }
+}
+
+impl Default for Rules {
+  fn default() -> Self {
+    Rules::sized(::DEFAULT_SIZE, ::DEFAULT_SIZE)
+  }
+}
+
+/// A line sent between the host of a match and the player who joined it, or someone watching.
+///
+/// Every message is one line of words separated by spaces, starting with the message's name. See
+/// `notation` for how boards, hands and cards are written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+  /// `hello <version> <width> <height> <hand size>`: sent by the host when a player connects.
+  Hello(u32, Rules),
+  /// `join <version> <hand>`: the joining player's reply, with the cards they bring.
+  Join(u32, Vec<Card>),
+  /// `watch <version>`: sent instead of `join` by someone who only wants to watch.
+  Watch(u32),
+  /// `token <token>`: sent after a match starts. The receiver can use it to come back to the match
+  /// if their connection is lost.
+  Token(String),
+  /// `resume <version> <token> <placements>`: sent first by a player coming back to a match, with
+  /// how many placements they saw. They are sent everything they missed.
+  Resume(u32, String, usize),
+  /// `away`: the other player's connection was lost, and the match is waiting for them to come back.
+  Away,
+  /// `start <color> <state>`: the match has started, and the receiver plays the given color.
+  Start(Color, GameState),
+  /// `move <card> <row> <column> <row,column>`: the sender wants to play a move. Players leave out
+  /// the card to battle first, and pick it with `first` when asked. The host gives it when it
+  /// announces a move before the joining player reveals their seed, so the move can't change
+  /// afterwards.
+  Move(Move, Option<(usize, usize)>),
+  /// `choose <row,column>...`: the card just played can battle more than one card, so the player
+  /// who played it must pick which to battle first.
+  Choose(Vec<(usize, usize)>),
+  /// `first <row,column>`: the card to battle first.
+  First((usize, usize)),
+  /// `placed <color> <card> <row> <column> <card>`: a move was played, with the card it played.
+  Placed(Color, Move, Card),
+  /// `battle <attacker> <defender> <attack> <defense> <result> <flipped>...`: a battle was fought.
+  Battle(BattleReport),
+  /// `taken <row,column>...`: cards were taken without a battle.
+  Taken(Vec<(usize, usize)>),
+  /// `state <state>`: the match after a move. This replaces the receiver's copy.
+  State(GameState),
+  /// `commit <seed>`: the first link of the sender's chain of seeds, sent when the match starts.
+  Commit(Seed),
+  /// `seed <placement> <seed>`: the sender's seed for a placement. The player placing a card only
+  /// learns the other side's seed after their move is sent.
+  Seed(usize, Seed),
+  /// `list`: asks a lobby for its open tables.
+  List,
+  /// `tables <id>,<width>,<height>,<hand size>...`: the tables open in a lobby.
+  Tables(Vec<(usize, Rules)>),
+  /// `create <version> <width> <height> <hand size>`: opens a table in a lobby with the given rules.
+  Create(u32, Rules),
+  /// `opened <id>`: the sender's table is open, and waiting for someone to sit at it.
+  Opened(usize),
+  /// `sit <id>`: sits at an open table in a lobby. Both players are then sent `hello`, and the
+  /// match goes on as if one of them were hosting it.
+  Sit(usize),
+  /// `left`: the other player left the match, and the receiver is back in the lobby.
+  Left,
+  /// `illegal <reason>`: the last message broke the rules, and was ignored.
+  Illegal(String),
+  /// `bye`: the sender is leaving.
+  Bye
+}
+
+fn format_positions(positions: &[(usize, usize)]) -> String {
+  positions.iter().map(|p| format_position(*p)).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_positions(words: &[&str]) -> Option<Vec<(usize, usize)>> {
+  words.iter().map(|w| parse_position(w)).collect()
+}
+
+fn format_rules(rules: &Rules) -> String {
+  format!("{} {} {}", rules.width, rules.height, rules.hand_size)
+}
+
+/// Reads the width, height and hand size of some rules.
+fn parse_rules(words: &[&str]) -> Option<Rules> {
+  let numbers: Vec<usize> = match words.iter().map(|w| w.parse()).collect() {
+    Ok(n) => n,
+    Err(_) => return None
+  };
+  if numbers.len() != 3 || numbers[0] == 0 || numbers[1] == 0 {
+    return None;
+  }
+  Some(Rules {
+    width: numbers[0],
+    height: numbers[1],
+    hand_size: numbers[2]
+  })
+}
+
+fn format_result(result: BattleResult) -> &'static str {
+  match result {
+    BattleResult::Attacker => "attacker",
+    BattleResult::Defender => "defender",
+    BattleResult::Draw => "draw"
+  }
+}
+
+fn parse_result(text: &str) -> Option<BattleResult> {
+  match text {
+    "attacker" => Some(BattleResult::Attacker),
+    "defender" => Some(BattleResult::Defender),
+    "draw" => Some(BattleResult::Draw),
+    _ => None
+  }
+}
+
+impl ToString for Message {
+  fn to_string(&self) -> String {
+    match *self {
+      Message::Hello(version, ref rules) => format!("hello {} {}", version, format_rules(rules)),
+      Message::Join(version, ref hand) => format!("join {} {}", version, format_hand(hand)),
+      Message::Watch(version) => format!("watch {}", version),
+      Message::Token(ref token) => format!("token {}", token),
+      Message::Resume(version, ref token, placements) => format!("resume {} {} {}", version, token, placements),
+      Message::Away => "away".to_string(),
+      Message::Start(color, ref state) => format!("start {} {}", format_color(color), format_state(state)),
+      Message::Move(ref mv, None) => format!("move {}", format_move(mv)),
+      Message::Move(ref mv, Some(first)) => format!("move {} {}", format_move(mv), format_position(first)),
+      Message::Choose(ref targets) => format!("choose {}", format_positions(targets)),
+      Message::First(pos) => format!("first {}", format_position(pos)),
+      Message::Placed(color, ref mv, ref card) => format!("placed {} {} {}", format_color(color), format_move(mv), format_card(card)),
+      Message::Battle(ref b) => {
+        let mut words = vec![
+          "battle".to_string(),
+          format_position(b.attacker),
+          format_position(b.defender),
+          b.attack.to_string(),
+          b.defense.to_string(),
+          format_result(b.result).to_string()
+        ];
+        words.extend(b.flipped.iter().map(|p| format_position(*p)));
+        words.join(" ")
+      },
+      Message::Taken(ref taken) => {
+        let mut words = vec!["taken".to_string()];
+        words.extend(taken.iter().map(|p| format_position(*p)));
+        words.join(" ")
+      },
+      Message::State(ref state) => format!("state {}", format_state(state)),
+      Message::Commit(ref seed) => format!("commit {}", to_hex(seed)),
+      Message::Seed(placement, ref seed) => format!("seed {} {}", placement, to_hex(seed)),
+      Message::List => "list".to_string(),
+      Message::Tables(ref tables) => {
+        let mut words = vec!["tables".to_string()];
+        words.extend(tables.iter().map(|&(id, ref rules)| format!("{},{}", id, format_rules(rules).replace(' ', ","))));
+        words.join(" ")
+      },
+      Message::Create(version, ref rules) => format!("create {} {}", version, format_rules(rules)),
+      Message::Opened(id) => format!("opened {}", id),
+      Message::Sit(id) => format!("sit {}", id),
+      Message::Left => "left".to_string(),
+      Message::Illegal(ref reason) => format!("illegal {}", reason),
+      Message::Bye => "bye".to_string()
+    }
+  }
+}
+
+impl Message {
+  pub fn parse(line: &str) -> Option<Message> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let rest = |n: usize| words[n..].join(" ");
+    let number = |n: usize| words.get(n).and_then(|w| w.parse::<usize>().ok());
+    match words.first().cloned() {
+      Some("hello") if words.len() == 5 => match (words[1].parse(), parse_rules(&words[2..])) {
+        (Ok(version), Some(rules)) => Some(Message::Hello(version, rules)),
+        _ => None
+      },
+      Some("join") if words.len() == 3 => match (words[1].parse(), parse_hand(words[2])) {
+        (Ok(version), Some(hand)) => Some(Message::Join(version, hand)),
+        _ => None
+      },
+      Some("watch") if words.len() == 2 => words[1].parse().ok().map(Message::Watch),
+      Some("token") if words.len() == 2 => Some(Message::Token(words[1].to_string())),
+      Some("resume") if words.len() == 4 => match (words[1].parse(), number(3)) {
+        (Ok(version), Some(placements)) => Some(Message::Resume(version, words[2].to_string(), placements)),
+        _ => None
+      },
+      Some("away") if words.len() == 1 => Some(Message::Away),
+      Some("start") if words.len() > 2 => match (parse_color(words[1]), parse_state(&rest(2))) {
+        (Some(color), Some(state)) => Some(Message::Start(color, state)),
+        _ => None
+      },
+      Some("move") if words.len() == 4 => parse_move(&rest(1)).map(|mv| Message::Move(mv, None)),
+      Some("move") if words.len() == 5 => match (parse_move(&words[1..4].join(" ")), parse_position(words[4])) {
+        (Some(mv), Some(first)) => Some(Message::Move(mv, Some(first))),
+        _ => None
+      },
+      Some("choose") => parse_positions(&words[1..]).map(Message::Choose),
+      Some("first") if words.len() == 2 => parse_position(words[1]).map(Message::First),
+      Some("placed") if words.len() == 6 => match (parse_color(words[1]), parse_move(&words[2..5].join(" ")), parse_card(words[5])) {
+        (Some(color), Some(mv), Some(card)) => Some(Message::Placed(color, mv, card)),
+        _ => None
+      },
+      Some("battle") if words.len() >= 6 => {
+        let parsed = (
+          parse_position(words[1]),
+          parse_position(words[2]),
+          words[3].parse(),
+          words[4].parse(),
+          parse_result(words[5]),
+          parse_positions(&words[6..])
+        );
+        match parsed {
+          (Some(attacker), Some(defender), Ok(attack), Ok(defense), Some(result), Some(flipped)) => Some(Message::Battle(BattleReport {
+            attacker: attacker,
+            defender: defender,
+            attack: attack,
+            defense: defense,
+            result: result,
+            flipped: flipped
+          })),
+          _ => None
+        }
+      },
+      Some("taken") => parse_positions(&words[1..]).map(Message::Taken),
+      Some("state") => parse_state(&rest(1)).map(Message::State),
+      Some("commit") if words.len() == 2 => from_hex(words[1]).map(Message::Commit),
+      Some("seed") if words.len() == 3 => match (number(1), from_hex(words[2])) {
+        (Some(placement), Some(seed)) => Some(Message::Seed(placement, seed)),
+        _ => None
+      },
+      Some("list") if words.len() == 1 => Some(Message::List),
+      Some("tables") => {
+        let tables: Option<Vec<(usize, Rules)>> = words[1..].iter()
+          .map(|w| {
+            let parts: Vec<&str> = w.split(',').collect();
+            match (parts[0].parse().ok(), parse_rules(&parts[1..])) {
+              (Some(id), Some(rules)) => Some((id, rules)),
+              _ => None
+            }
+          })
+          .collect();
+        tables.map(Message::Tables)
+      },
+      Some("create") if words.len() == 5 => match (words[1].parse(), parse_rules(&words[2..])) {
+        (Ok(version), Some(rules)) => Some(Message::Create(version, rules)),
+        _ => None
+      },
+      Some("opened") if words.len() == 2 => number(1).map(Message::Opened),
+      Some("sit") if words.len() == 2 => number(1).map(Message::Sit),
+      Some("left") if words.len() == 1 => Some(Message::Left),
+      Some("illegal") => Some(Message::Illegal(rest(1))),
+      Some("bye") => Some(Message::Bye),
+      _ => None
+    }
+  }
+}
+
+/// The messages that tell a player about a move and everything it caused, ending with the state
+/// of the match afterwards.
+pub fn report_messages(color: Color, mv: Move, card: Card, report: &PlacementReport, state: &GameState) -> Vec<Message> {
+  let mut messages = vec![Message::Placed(color, mv, card)];
+  messages.extend(report.battles.iter().cloned().map(Message::Battle));
+  if !report.taken.is_empty() {
+    messages.push(Message::Taken(report.taken.clone()));
+  }
+  messages.push(Message::State(state.clone()));
+  messages
+}
+
+/// A connection to another player, sending and receiving whole messages.
+///
+/// Messages are read on a separate thread, so they can be checked for without blocking. Lines that
+/// are not messages are skipped. When the other side goes away, `Bye` is received.
+pub struct Connection {
+  stream: TcpStream,
+  incoming: Receiver<Message>,
+  /// Set when the connection ended without the other side saying `bye`.
+  lost: Arc<AtomicBool>
+}
+
+impl Connection {
+  pub fn new(stream: TcpStream) -> io::Result<Connection> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let (tx, rx) = channel();
+    let lost = Arc::new(AtomicBool::new(false));
+    let reader_lost = lost.clone();
+    thread::spawn(move || {
+      let mut said_bye = false;
+      for line in reader.lines() {
+        let line = match line {
+          Ok(l) => l,
+          Err(_) => break
+        };
+        if let Some(message) = Message::parse(&line) {
+          said_bye |= message == Message::Bye;
+          if tx.send(message).is_err() {
+            return;
+          }
+        }
+      }
+      reader_lost.store(!said_bye, Ordering::SeqCst);
+      let _ = tx.send(Message::Bye);
+    });
+    Ok(Connection {
+      stream: stream,
+      incoming: rx,
+      lost: lost
+    })
+  }
+
+  /// Whether the connection ended without the other side saying `bye`, so they may be back.
+  ///
+  /// This is known by the time the `Bye` for the end of the connection is received.
+  pub fn lost(&self) -> bool {
+    self.lost.load(Ordering::SeqCst)
+  }
+
+  pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Connection> {
+    Connection::new(TcpStream::connect(addr)?)
+  }
+
+  pub fn send(&mut self, message: &Message) -> io::Result<()> {
+    writeln!(self.stream, "{}", message.to_string())?;
+    self.stream.flush()
+  }
+
+  /// Gets the next message if one has arrived, without waiting.
+  pub fn try_recv(&self) -> Option<Message> {
+    match self.incoming.try_recv() {
+      Ok(m) => Some(m),
+      Err(TryRecvError::Empty) => None,
+      Err(TryRecvError::Disconnected) => Some(Message::Bye)
+    }
+  }
+
+  /// Waits for the next message.
+  pub fn recv(&self) -> Message {
+    self.incoming.recv().unwrap_or(Message::Bye)
+  }
+
+  pub fn peer_addr(&self) -> io::Result<::std::net::SocketAddr> {
+    self.stream.peer_addr()
+  }
+}
+
+impl Drop for Connection {
+  /// Closes the connection, which the reading thread also holds open.
+  fn drop(&mut self) {
+    let _ = self.stream.shutdown(Shutdown::Both);
+  }
+}
+
+/// Waits for one player to connect on the given address, on another thread.
+pub fn accept_one<A: ToSocketAddrs>(addr: A) -> io::Result<Receiver<io::Result<Connection>>> {
+  let listener = TcpListener::bind(addr)?;
+  let (tx, rx) = channel();
+  thread::spawn(move || {
+    let connection = listener.accept().and_then(|(stream, _)| Connection::new(stream));
+    let _ = tx.send(connection);
+  });
+  Ok(rx)
+}
+
+/// Connects to the given address on another thread.
+pub fn connect_in_background<A: ToSocketAddrs + Send + 'static>(addr: A) -> Receiver<io::Result<Connection>> {
+  let (tx, rx) = channel();
+  thread::spawn(move || {
+    let _ = tx.send(Connection::connect(addr));
+  });
+  rx
+}
+
+/// Makes a token a player can use to come back to a match.
+pub fn new_token<R: Rng>(rng: &mut R) -> String {
+  let mut token = [0; 32];
+  rng.fill_bytes(&mut token);
+  to_hex(&token)
+}
+
+/// Keeps waiting for players to connect on the given address, on another thread.
+pub fn accept_all<A: ToSocketAddrs>(addr: A) -> io::Result<Receiver<io::Result<Connection>>> {
+  let listener = TcpListener::bind(addr)?;
+  let (tx, rx) = channel();
+  thread::spawn(move || {
+    for stream in listener.incoming() {
+      let connection = stream.and_then(Connection::new);
+      if tx.send(connection).is_err() {
+        return;
+      }
+    }
+  });
+  Ok(rx)
+}
+
+/// Everyone watching a hosted match.
+///
+/// Spectators are sent what the players are told about the match, starting with a snapshot of it
+/// when they begin watching. They are only ever read from here, so nothing they send can reach the
+/// match.
+#[derive(Default)]
+pub struct Spectators {
+  /// Each connection, and whether it has asked to watch yet.
+  connections: Vec<(Connection, bool)>
+}
+
+impl Spectators {
+  pub fn new() -> Self {
+    Spectators::default()
+  }
+
+  /// Takes a connection that came in after the match already had its players, telling it the rules.
+  /// Nothing else is sent until it asks to watch.
+  pub fn add(&mut self, mut connection: Connection, rules: Rules) {
+    if connection.send(&Message::Hello(PROTOCOL_VERSION, rules)).is_ok() {
+      self.connections.push((connection, false));
+    }
+  }
+
+  /// Starts sending a connection that asked to watch everything about the match, beginning with
+  /// its state if one is being played.
+  pub fn watch(&mut self, mut connection: Connection, state: Option<&GameState>) {
+    let sent = match state {
+      Some(state) => connection.send(&Message::State(state.clone())),
+      None => Ok(())
+    };
+    if sent.is_ok() {
+      self.connections.push((connection, true));
+    }
+  }
+
+  /// Sends a message to everyone watching. Anyone who can't be reached is dropped.
+  pub fn send(&mut self, message: &Message) {
+    let connections = self.connections.drain(..).collect::<Vec<_>>();
+    for (mut connection, watching) in connections {
+      if !watching || connection.send(message).is_ok() {
+        self.connections.push((connection, watching));
+      }
+    }
+  }
+
+  /// Reads everything spectators sent, answering requests to watch and turning everything else
+  /// away.
+  pub fn update(&mut self, state: Option<&GameState>) {
+    let mut i = 0;
+    while i < self.connections.len() {
+      let mut left = false;
+      while let Some(message) = self.connections[i].0.try_recv() {
+        let (ref mut connection, ref mut watching) = self.connections[i];
+        let reply = match message {
+          Message::Watch(version) if version != PROTOCOL_VERSION => Message::Illegal(format!("version {} is needed", PROTOCOL_VERSION)),
+          Message::Watch(_) if *watching => continue,
+          Message::Watch(_) => {
+            *watching = true;
+            match state {
+              Some(state) => Message::State(state.clone()),
+              None => continue
+            }
+          },
+          Message::Join(..) => Message::Illegal("the match already has two players".to_string()),
+          Message::Bye => {
+            left = true;
+            break;
+          },
+          _ => Message::Illegal("spectators can't play".to_string())
+        };
+        if connection.send(&reply).is_err() {
+          left = true;
+          break;
+        }
+      }
+      if left {
+        self.connections.remove(i);
+      } else {
+        i += 1;
+      }
+    }
+  }
+
+  /// How many people are watching.
+  pub fn len(&self) -> usize {
+    self.connections.iter().filter(|&&(_, watching)| watching).count()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+/// What came of a player trying to play a move.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Played {
+  /// The card can battle more than one card, and the player must pick which to battle first.
+  Choose(Vec<(usize, usize)>),
+  /// The move can't be rolled until the joining player reveals their seed for it. These messages
+  /// ask for it.
+  Waiting(Vec<Message>),
+  /// The move was played. These messages tell both players what happened.
+  Done(Vec<Message>)
+}
+
+/// The host's copy of a match, which all moves go through.
+///
+/// The host rolls every battle and checks every move against the rules, so a player can't play a
+/// card they don't have, play out of turn or fill a space that is taken.
+///
+/// The rolls for each placement come from a seed from each side, revealed once the move can't
+/// change, so the joining player can check the host never picked its rolls. See `fair`.
+pub struct HostedMatch {
+  pub state: GameState,
+  /// The color the joining player plays.
+  joiner: Color,
+  seeds: SeedChain,
+  joiner_seeds: Option<ChainVerifier>,
+  /// How many placements have been made.
+  placements: usize,
+  /// A move waiting for its player to pick which card to battle first.
+  pending: Option<Move>,
+  /// A move ready to be rolled once the joining player's seed is in, and the card to battle first.
+  ready: Option<(Move, Option<(usize, usize)>)>,
+  /// The messages sent about each placement, so a player coming back can catch up.
+  log: Vec<Vec<Message>>
+}
+
+impl HostedMatch {
+  /// Starts hosting a match against a player of the given color. The chain of seeds needs a seed
+  /// for every space on the board.
+  pub fn new(state: GameState, joiner: Color, seeds: SeedChain) -> Self {
+    HostedMatch {
+      state: state,
+      joiner: joiner,
+      seeds: seeds,
+      joiner_seeds: None,
+      placements: 0,
+      pending: None,
+      ready: None,
+      log: Vec::new()
+    }
+  }
+
+  /// The message that commits the host to its seeds, to send when the match starts.
+  pub fn commitment(&self) -> Message {
+    Message::Commit(self.seeds.commitment())
+  }
+
+  /// Takes the joining player's commitment to their seeds. The same commitment may be sent again.
+  pub fn commit(&mut self, commitment: Seed) -> Result<(), String> {
+    if let Some(ref joiner) = self.joiner_seeds {
+      return if joiner.commitment() == commitment {
+        Ok(())
+      } else {
+        Err("you already committed to your seeds".to_string())
+      };
+    }
+    self.joiner_seeds = Some(ChainVerifier::new(commitment));
+    Ok(())
+  }
+
+  /// The move waiting for a pick of which card to battle first, and the cards it could battle.
+  pub fn pending(&self) -> Option<(Move, Vec<(usize, usize)>)> {
+    self.pending.map(|mv| (mv, self.state.battle_targets(mv)))
+  }
+
+  /// Plays a move for the given color.
+  pub fn play(&mut self, color: Color, mv: Move) -> Result<Played, String> {
+    if self.state.to_move != color {
+      return Err("it is not your turn".to_string());
+    }
+    if self.pending.is_some() {
+      return Err("pick which card to battle first".to_string());
+    }
+    if self.ready.is_some() {
+      return Err("a move is already being played".to_string());
+    }
+    if !self.state.is_legal(mv) {
+      return Err(format!("{} is not a legal move", format_move(&mv)));
+    }
+    let targets = self.state.battle_targets(mv);
+    if targets.len() > 1 {
+      self.pending = Some(mv);
+      return Ok(Played::Choose(targets));
+    }
+    Ok(self.ready_to_roll(mv, None))
+  }
+
+  /// Picks which card to battle first for the given color's pending move.
+  pub fn choose(&mut self, color: Color, first: (usize, usize)) -> Result<Played, String> {
+    let mv = match self.pending {
+      Some(mv) if self.state.to_move == color => mv,
+      _ => return Err("there is nothing to choose".to_string())
+    };
+    if !self.state.battle_targets(mv).contains(&first) {
+      return Err(format!("{} can't be battled", format_position(first)));
+    }
+    self.pending = None;
+    Ok(self.ready_to_roll(mv, Some(first)))
+  }
+
+  /// The messages that bring a joining player who saw the given number of placements back to where
+  /// the match is: everything sent about the placements they missed, then whatever they must answer.
+  pub fn resume(&self, placements: usize) -> Result<Vec<Message>, String> {
+    if placements > self.log.len() {
+      return Err(format!("only {} placements have been made", self.log.len()));
+    }
+    let mut messages: Vec<Message> = self.log[placements..].iter().flat_map(|m| m.iter().cloned()).collect();
+    if let Some((_, targets)) = self.pending() {
+      if self.state.to_move == self.joiner {
+        messages.push(Message::Choose(targets));
+      }
+    }
+    // Any move waiting to be rolled is waiting for the joining player's seed.
+    if let Some((mv, first)) = self.ready {
+      messages.push(Message::Move(mv, first));
+    }
+    Ok(messages)
+  }
+
+  /// Takes a seed revealed by the joining player, rolling the move waiting for it.
+  pub fn reveal(&mut self, placement: usize, seed: Seed) -> Result<Played, String> {
+    match self.joiner_seeds {
+      Some(ref mut joiner) => joiner.check(placement, seed)?,
+      None => return Err("commit to your seeds first".to_string())
+    }
+    Ok(self.roll_if_ready())
+  }
+
+  /// Notes that a move can no longer change. The host's own moves are announced to the joining
+  /// player with the card they battle first, and the joining player answers with their seed.
+  fn ready_to_roll(&mut self, mv: Move, first: Option<(usize, usize)>) -> Played {
+    self.ready = Some((mv, first));
+    match self.roll_if_ready() {
+      Played::Waiting(mut messages) => {
+        if self.state.to_move != self.joiner {
+          messages.push(Message::Move(mv, first));
+        }
+        Played::Waiting(messages)
+      },
+      played => played
+    }
+  }
+
+  fn roll_if_ready(&mut self) -> Played {
+    let placement = self.placements + 1;
+    let joiner = self.joiner_seeds.as_ref().and_then(|j| j.get(placement));
+    let ((mv, first), joiner) = match (self.ready, joiner) {
+      (Some(ready), Some(joiner)) => (ready, joiner),
+      _ => return Played::Waiting(Vec::new())
+    };
+    let host = self.seeds.reveal(placement).expect("Chain is too short for the board");
+    self.ready = None;
+    self.placements = placement;
+    let color = self.state.to_move;
+    let card = self.state.hand(color)[mv.card];
+    let report = self.state.apply_reported(mv, first, &mut placement_rng(&host, &joiner)).expect("Move was checked");
+    let mut messages = vec![Message::Seed(placement, host)];
+    messages.extend(report_messages(color, mv, card, &report, &self.state));
+    self.log.push(messages.clone());
+    Played::Done(messages)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use seeded_rng;
+  use fair::RollChecker;
+  use fixtures::{crowded_match, hand};
+  use generator::BoardGenerator;
+
+  #[test]
+  fn every_message_round_trips() {
+    let state = crowded_match(5);
+    let card = state.blue_hand[0];
+    let rules = Rules::sized(5, 3);
+    let messages = vec![
+      Message::Hello(PROTOCOL_VERSION, rules),
+      Message::Join(PROTOCOL_VERSION, state.blue_hand.clone()),
+      Message::Watch(PROTOCOL_VERSION),
+      Message::Token("0a1b2c".to_string()),
+      Message::Resume(PROTOCOL_VERSION, "0a1b2c".to_string(), 4),
+      Message::Away,
+      Message::Start(Color::Red, state.clone()),
+      Message::Move(Move::new(2, 3, 4), None),
+      Message::Move(Move::new(0, 1, 2), Some((2, 2))),
+      Message::Choose(vec![(1, 2), (2, 1)]),
+      Message::First((2, 1)),
+      Message::Placed(Color::Blue, Move::new(1, 2, 2), card),
+      Message::Battle(BattleReport {
+        attacker: (2, 2),
+        defender: (1, 2),
+        attack: 140,
+        defense: 37,
+        result: BattleResult::Attacker,
+        flipped: vec![(1, 2), (1, 3)]
+      }),
+      Message::Battle(BattleReport {
+        attacker: (2, 2),
+        defender: (2, 3),
+        attack: 12,
+        defense: 12,
+        result: BattleResult::Draw,
+        flipped: Vec::new()
+      }),
+      Message::Taken(vec![(3, 2)]),
+      Message::State(state),
+      Message::Commit([1; 32]),
+      Message::Seed(3, [0xAB; 32]),
+      Message::List,
+      Message::Tables(vec![(1, rules), (4, Rules::default())]),
+      Message::Tables(Vec::new()),
+      Message::Create(PROTOCOL_VERSION, rules),
+      Message::Opened(7),
+      Message::Sit(7),
+      Message::Left,
+      Message::Illegal("it is not your turn".to_string()),
+      Message::Bye
+    ];
+    for message in messages {
+      assert_eq!(Message::parse(&message.to_string()), Some(message.clone()), "{}", message.to_string());
+    }
+  }
+
+  #[test]
+  fn broken_messages_are_not_parsed() {
+    for line in &["", "hello 3 4 4", "move 0 1", "move 0 1 1 x", "first 1", "seed 1 00", "placed blue 0 1 1", "sit x"] {
+      assert_eq!(Message::parse(line), None, "{}", line);
+    }
+  }
+
+  /// Plays the joining side of a match: the first legal move each turn and the first card offered
+  /// to battle first, checking every roll. Returns how many cards were placed and how the match
+  /// ended.
+  fn join(mut connection: Connection, hand: Vec<Card>) -> Result<(usize, GameState), String> {
+    let mut checker: Option<RollChecker> = None;
+    let mut placements = 0;
+    loop {
+      let message = connection.recv();
+      if let Some(ref mut checker) = checker {
+        checker.observe(&message)?;
+      }
+      let replies = match message {
+        Message::Hello(..) => vec![Message::Join(PROTOCOL_VERSION, hand.clone())],
+        Message::Start(color, state) => {
+          let mut own = RollChecker::new(SeedChain::new(16, &mut seeded_rng(3)), state.clone());
+          let mut replies = vec![own.commitment()];
+          if state.to_move == color {
+            let mv = state.legal_moves()[0];
+            own.announce(mv, None);
+            replies.push(Message::Move(mv, None));
+            replies.extend(own.next_seed());
+          }
+          checker = Some(own);
+          replies
+        },
+        Message::Move(..) => checker.as_ref().and_then(RollChecker::next_seed).into_iter().collect(),
+        Message::Choose(targets) => {
+          if let Some(ref mut checker) = checker {
+            checker.announce_first(targets[0]);
+          }
+          vec![Message::First(targets[0])]
+        },
+        Message::Placed(..) => {
+          placements += 1;
+          Vec::new()
+        },
+        Message::State(ref state) if state.is_over() => return Ok((placements, state.clone())),
+        Message::State(ref state) if state.to_move == Color::Red => {
+          let mv = state.legal_moves()[0];
+          let mut replies = vec![Message::Move(mv, None)];
+          if let Some(ref mut checker) = checker {
+            checker.announce(mv, None);
+            replies.extend(checker.next_seed());
+          }
+          replies
+        },
+        Message::Illegal(reason) => return Err(reason),
+        Message::Bye => return Err("the host left".to_string()),
+        _ => Vec::new()
+      };
+      for reply in replies {
+        connection.send(&reply).map_err(|e| e.to_string())?;
+      }
+    }
+  }
+
+  #[test]
+  fn loopback_match() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let joiner = Connection::connect(listener.local_addr().unwrap()).unwrap();
+    let mut host = Connection::new(listener.accept().unwrap().0).unwrap();
+    let mut rng = seeded_rng(11);
+    let joiner_hand = hand(&mut rng, 5);
+    let joined = thread::spawn(move || join(joiner, joiner_hand));
+
+    host.send(&Message::Hello(PROTOCOL_VERSION, Rules::default())).unwrap();
+    let red = match host.recv() {
+      Message::Join(PROTOCOL_VERSION, hand) => hand,
+      message => panic!("{:?} isn't a join", message)
+    };
+    let blue = hand(&mut rng, 5);
+    let state = GameState::new(BoardGenerator::new().seed(11).generate(), blue, red, Color::Blue);
+    let mut hosted = HostedMatch::new(state.clone(), Color::Red, SeedChain::new(16, &mut seeded_rng(4)));
+    host.send(&Message::Start(Color::Red, state)).unwrap();
+    host.send(&hosted.commitment()).unwrap();
+
+    // Whether the host announced its move and is waiting for the joiner's seed.
+    let mut announced = false;
+    while !hosted.state.is_over() {
+      let played = if hosted.state.to_move == Color::Blue && !announced {
+        announced = true;
+        // The host battles the last card it can, so the pick isn't the usual order.
+        let mv = hosted.state.legal_moves()[0];
+        match hosted.play(Color::Blue, mv) {
+          Ok(Played::Choose(targets)) => hosted.choose(Color::Blue, *targets.last().unwrap()),
+          played => played
+        }
+      } else {
+        match host.recv() {
+          Message::Commit(commitment) => hosted.commit(commitment).map(|_| Played::Waiting(Vec::new())),
+          Message::Move(mv, _) => hosted.play(Color::Red, mv),
+          Message::First(first) => hosted.choose(Color::Red, first),
+          Message::Seed(placement, seed) => hosted.reveal(placement, seed),
+          message => panic!("The host didn't expect {:?}", message)
+        }
+      };
+      let messages = match played.unwrap() {
+        Played::Choose(targets) => vec![Message::Choose(targets)],
+        Played::Waiting(messages) => messages,
+        Played::Done(messages) => {
+          announced = false;
+          messages
+        }
+      };
+      for message in messages {
+        host.send(&message).unwrap();
+      }
+    }
+
+    let (placements, checked) = joined.join().unwrap().unwrap();
+    assert_eq!(checked, hosted.state);
+    // Every card of both hands was played.
+    assert_eq!(placements, 10);
+  }
+}
@@ -0,0 +1,169 @@
+use {Arrows, Board, Card, Color, OwnedCard, PlacedCard, Space, TetraMaster};
+use state::{GameState, Move};
+
+/// Writes a card as its stats and its arrows in hex, like `1M23:a5`.
+pub fn format_card(card: &Card) -> String {
+  format!("{}:{:02x}", card.to_string(), card.arrows.flags)
+}
+
+pub fn parse_card(text: &str) -> Option<Card> {
+  let mut parts = text.splitn(2, ':');
+  let mut card = match parts.next().and_then(TetraMaster::parse_card) {
+    Some(c) => c,
+    None => return None
+  };
+  card.arrows = match parts.next().map(|a| u8::from_str_radix(a, 16)) {
+    Some(Ok(flags)) => Arrows::from_flags(flags),
+    _ => return None
+  };
+  Some(card)
+}
+
+pub fn format_color(color: Color) -> &'static str {
+  match color {
+    Color::Blue => "blue",
+    Color::Red => "red"
+  }
+}
+
+pub fn parse_color(text: &str) -> Option<Color> {
+  match text {
+    "blue" | "b" => Some(Color::Blue),
+    "red" | "r" => Some(Color::Red),
+    _ => None
+  }
+}
+
+fn color_char(color: Color) -> char {
+  match color {
+    Color::Blue => 'b',
+    Color::Red => 'r'
+  }
+}
+
+/// Writes a space as `.` if it is empty, `#` if it is a block, or the card's owner, the player who
+/// brought it and the card, like `rb1M23:a5` for a blue card taken by red.
+pub fn format_space(space: &Space) -> String {
+  match *space {
+    Space::Empty => ".".to_string(),
+    Space::Block => "#".to_string(),
+    Space::Card(ref c) => format!("{}{}{}", color_char(c.color), color_char(c.original_color), format_card(&c.card.card))
+  }
+}
+
+/// Reads a space written by `format_space`. Cards are given no location.
+pub fn parse_space(text: &str) -> Option<Space> {
+  match text {
+    "." => return Some(Space::Empty),
+    "#" => return Some(Space::Block),
+    _ => {}
+  }
+  if text.len() < 2 || !text.is_char_boundary(2) {
+    return None;
+  }
+  let (color, original) = match (parse_color(&text[..1]), parse_color(&text[1..2])) {
+    (Some(c), Some(o)) => (c, o),
+    _ => return None
+  };
+  parse_card(&text[2..]).map(|card| {
+    let mut card = OwnedCard::new(card, original);
+    card.color = color;
+    Space::Card(PlacedCard::new(card, 0, 0))
+  })
+}
+
+/// Writes a board as its rows separated by `/`, with the spaces of each row separated by `,`.
+///
+/// The board has no whitespace in it, so it can be used as one word of a line.
+pub fn format_board(board: &Board) -> String {
+  board.spaces
+    .iter()
+    .map(|row| row.iter().map(format_space).collect::<Vec<_>>().join(","))
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+pub fn parse_board(text: &str) -> Option<Board> {
+  let rows: Vec<Vec<&str>> = text.split('/').map(|row| row.split(',').collect()).collect();
+  let (width, height) = (rows[0].len(), rows.len());
+  if text.is_empty() || rows.iter().any(|row| row.len() != width) {
+    return None;
+  }
+  let mut board = Board::new(width, height);
+  for (r, row) in rows.iter().enumerate() {
+    for (c, text) in row.iter().enumerate() {
+      match parse_space(text) {
+        Some(Space::Card(card)) => {
+          board.add_card(r + 1, c + 1, card.card);
+        },
+        Some(space) => *board.space_mut(r + 1, c + 1) = space,
+        None => return None
+      }
+    }
+  }
+  Some(board)
+}
+
+/// Writes a hand as its cards separated by `,`, or `-` if it is empty.
+pub fn format_hand(hand: &[Card]) -> String {
+  if hand.is_empty() {
+    return "-".to_string();
+  }
+  hand.iter().map(format_card).collect::<Vec<_>>().join(",")
+}
+
+pub fn parse_hand(text: &str) -> Option<Vec<Card>> {
+  if text == "-" {
+    return Some(Vec::new());
+  }
+  text.split(',').map(parse_card).collect()
+}
+
+/// Writes a game as its board, blue's hand, red's hand and the color to move, separated by spaces.
+pub fn format_state(state: &GameState) -> String {
+  format!("{} {} {} {}",
+    format_board(&state.board),
+    format_hand(&state.blue_hand),
+    format_hand(&state.red_hand),
+    format_color(state.to_move))
+}
+
+pub fn parse_state(text: &str) -> Option<GameState> {
+  let parts: Vec<&str> = text.split_whitespace().collect();
+  if parts.len() != 4 {
+    return None;
+  }
+  match (parse_board(parts[0]), parse_hand(parts[1]), parse_hand(parts[2]), parse_color(parts[3])) {
+    (Some(board), Some(blue), Some(red), Some(to_move)) => Some(GameState::new(board, blue, red, to_move)),
+    _ => None
+  }
+}
+
+/// Writes a location as its row and column, like `2,3`.
+pub fn format_position(pos: (usize, usize)) -> String {
+  format!("{},{}", pos.0, pos.1)
+}
+
+pub fn parse_position(text: &str) -> Option<(usize, usize)> {
+  let mut parts = text.splitn(2, ',');
+  match (parts.next().map(str::parse), parts.next().map(str::parse)) {
+    (Some(Ok(row)), Some(Ok(column))) => Some((row, column)),
+    _ => None
+  }
+}
+
+/// Writes a move as the index of the card in hand, the row and the column, separated by spaces.
+pub fn format_move(mv: &Move) -> String {
+  format!("{} {} {}", mv.card, mv.row, mv.column)
+}
+
+pub fn parse_move(text: &str) -> Option<Move> {
+  let parts: Vec<usize> = match text.split_whitespace().map(str::parse).collect() {
+    Ok(p) => p,
+    Err(_) => return None
+  };
+  if parts.len() != 3 {
+    return None;
+  }
+  Some(Move::new(parts[0], parts[1], parts[2]))
+}
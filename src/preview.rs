@@ -0,0 +1,338 @@
+use {ArrowRelation, Board, Card, Color, OwnedCard, Space, TetraMaster, STAT_RANGES};
+
+/// The chance of each result of a single battle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BattleOdds {
+  pub attacker: f64,
+  pub defender: f64,
+  pub draw: f64
+}
+
+impl BattleOdds {
+  /// The chance the attacker wins in the end, since a draw is fought again.
+  pub fn attacker_wins(&self) -> f64 {
+    self.attacker / (self.attacker + self.defender)
+  }
+}
+
+/// The chance of each final score for a stat level.
+///
+/// The max score is picked evenly from the level's range, then the final score is picked evenly
+/// between 0 and the max.
+fn score_chances(level: u8) -> Vec<f64> {
+  let range = STAT_RANGES[level as usize];
+  let maxes = (range[1] - range[0]) as f64 + 1.0;
+  let mut chances = vec![0.0; range[1] as usize + 1];
+  for max in range[0] as usize..range[1] as usize + 1 {
+    let chance = 1.0 / (maxes * (max + 1) as f64);
+    for score in &mut chances[..max + 1] {
+      *score += chance;
+    }
+  }
+  chances
+}
+
+impl TetraMaster {
+  /// Works out the exact chance of each result of a battle, without rolling.
+  pub fn odds(attacker: &Card, defender: &Card) -> BattleOdds {
+    let attack = score_chances(attacker.offense_level());
+    let defense = score_chances(attacker.defense_level(defender));
+    let mut odds = BattleOdds {
+      attacker: 0.0,
+      defender: 0.0,
+      draw: 0.0
+    };
+    // The chance the defender scores below the current score.
+    let mut below = 0.0;
+    for (score, chance) in attack.iter().enumerate() {
+      let defended = defense.get(score).cloned().unwrap_or(0.0);
+      odds.attacker += chance * below;
+      odds.draw += chance * defended;
+      below += defended;
+    }
+    odds.defender = 1.0 - odds.attacker - odds.draw;
+    odds
+  }
+}
+
+/// What could happen if a card were placed in a space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview {
+  /// The chance of flipping each number of opposing cards, starting at none.
+  pub flips: Vec<f64>,
+  /// The chance the placed card ends up owned by the opponent.
+  pub captured: f64,
+  /// How many cards the placing player can expect to gain, counting the placed card as theirs
+  /// whether it is in their hand or on the board.
+  pub expected_gain: f64
+}
+
+impl Preview {
+  /// The number of flips with the highest chance.
+  pub fn likely_flips(&self) -> usize {
+    self.flips
+      .iter()
+      .enumerate()
+      .fold((0, 0.0), |best, (i, &p)| if p > best.1 { (i, p) } else { best })
+      .0
+  }
+}
+
+/// Finds the color of the card at the given location, the cards it would battle in order, and the
+/// cards it would take without a battle.
+fn targets(board: &Board, row: usize, col: usize) -> Option<(Color, Vec<(usize, usize)>, Vec<(usize, usize)>)> {
+  board.relations(row, col).map(|(color, relations)| {
+    let battles = relations.iter()
+      .filter(|&&(ref rel, _)| *rel == ArrowRelation::Battle)
+      .map(|&(_, pos)| pos)
+      .collect();
+    let takes = relations.iter()
+      .filter(|&&(ref rel, _)| *rel == ArrowRelation::Take)
+      .map(|&(_, pos)| pos)
+      .collect();
+    (color, battles, takes)
+  })
+}
+
+/// Every way the battles for a placed card could go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutcomeTree {
+  /// A battle against the card at the given location.
+  Battle {
+    defender: (usize, usize),
+    /// The chance of each result. When a draw would only fight this same battle again, its chance
+    /// is shared out between the other two results and `draw` is 0.
+    odds: BattleOdds,
+    won: Box<OutcomeTree>,
+    /// The board after losing. Losing ends the battles, so this is always `Done`.
+    lost: Box<OutcomeTree>,
+    /// What happens when a draw starts the battles over, if it can happen.
+    drawn: Option<Box<OutcomeTree>>
+  },
+  /// The battles are over, and any cards taken without a battle have been flipped.
+  Done(Board)
+}
+
+impl OutcomeTree {
+  /// Follows every way the battles for the card at the given location could go, like
+  /// `Board::run_battles_pos`.
+  fn resolve(board: &Board, row: usize, col: usize) -> OutcomeTree {
+    match targets(board, row, col) {
+      Some((color, battles, takes)) => OutcomeTree::resolve_from(board.clone(), row, col, color, &battles, &takes),
+      None => OutcomeTree::Done(board.clone())
+    }
+  }
+
+  fn resolve_from(board: Board, row: usize, col: usize, color: Color, battles: &[(usize, usize)], takes: &[(usize, usize)]) -> OutcomeTree {
+    let (r, c) = match battles.first() {
+      Some(&pos) => pos,
+      None => {
+        let mut board = board;
+        for &(r, c) in takes {
+          board.set_color(r, c, color);
+        }
+        return OutcomeTree::Done(board);
+      }
+    };
+    let mut odds = TetraMaster::odds(board.card(row, col).unwrap(), board.card(r, c).unwrap());
+    // A draw starts the battles over on the same board. If that would run the same battles as are
+    // left now, the draw only delays the result.
+    let restarts_here = targets(&board, row, col).map(|(_, fresh, _)| fresh == battles).unwrap_or(false);
+    if restarts_here {
+      odds = BattleOdds {
+        attacker: odds.attacker_wins(),
+        defender: 1.0 - odds.attacker_wins(),
+        draw: 0.0
+      };
+    }
+    let mut won = board.clone();
+    won.set_color(r, c, color);
+    won.do_combo(color, r, c);
+    let mut lost = board.clone();
+    let defender_color = lost.card(r, c).unwrap().color;
+    lost.set_color(row, col, defender_color);
+    lost.do_combo(defender_color, row, col);
+    let drawn = if odds.draw > 0.0 {
+      Some(Box::new(OutcomeTree::resolve(&board, row, col)))
+    } else {
+      None
+    };
+    OutcomeTree::Battle {
+      defender: (r, c),
+      odds: odds,
+      won: Box::new(OutcomeTree::resolve_from(won, row, col, color, &battles[1..], takes)),
+      lost: Box::new(OutcomeTree::Done(lost)),
+      drawn: drawn
+    }
+  }
+
+  /// Every board the battles could end on, with its chance. The same board may appear more than
+  /// once.
+  pub fn leaves(&self) -> Vec<(&Board, f64)> {
+    let mut leaves = Vec::new();
+    self.collect_leaves(1.0, &mut leaves);
+    leaves
+  }
+
+  fn collect_leaves<'a>(&'a self, chance: f64, leaves: &mut Vec<(&'a Board, f64)>) {
+    match *self {
+      OutcomeTree::Done(ref board) => leaves.push((board, chance)),
+      OutcomeTree::Battle { ref odds, ref won, ref lost, ref drawn, .. } => {
+        if odds.attacker > 0.0 {
+          won.collect_leaves(chance * odds.attacker, leaves);
+        }
+        if odds.defender > 0.0 {
+          lost.collect_leaves(chance * odds.defender, leaves);
+        }
+        if let Some(ref drawn) = *drawn {
+          drawn.collect_leaves(chance * odds.draw, leaves);
+        }
+      }
+    }
+  }
+
+  /// Every distinct board the battles could end on, with the chance of ending on it. The chances
+  /// add up to 1.
+  pub fn outcomes(&self) -> Vec<Outcome> {
+    let mut outcomes: Vec<Outcome> = Vec::new();
+    for (board, chance) in self.leaves() {
+      if let Some(outcome) = outcomes.iter_mut().find(|o| o.board == *board) {
+        outcome.chance += chance;
+        continue;
+      }
+      outcomes.push(Outcome {
+        board: board.clone(),
+        chance: chance
+      });
+    }
+    outcomes
+  }
+}
+
+/// A board a placement could end on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+  pub board: Board,
+  pub chance: f64
+}
+
+impl Board {
+  /// Works out every way placing the given card at the given location could go, without changing
+  /// the board.
+  ///
+  /// Returns `None` if the space is not empty.
+  pub fn outcome_tree(&self, row: usize, column: usize, card: OwnedCard) -> Option<OutcomeTree> {
+    match *self.space(row, column) {
+      Space::Empty => {},
+      _ => return None
+    }
+    let mut board = self.clone();
+    board.add_card(row, column, card);
+    Some(OutcomeTree::resolve(&board, row, column))
+  }
+
+  /// Works out every distinct board placing the given card at the given location could leave, with
+  /// its chance.
+  ///
+  /// Returns `None` if the space is not empty.
+  pub fn outcomes(&self, row: usize, column: usize, card: OwnedCard) -> Option<Vec<Outcome>> {
+    self.outcome_tree(row, column, card).map(|tree| tree.outcomes())
+  }
+
+  /// Works out what could happen if the given card were placed at the given location, without
+  /// changing the board.
+  ///
+  /// Returns `None` if the space is not empty.
+  pub fn preview(&self, row: usize, column: usize, card: OwnedCard) -> Option<Preview> {
+    let color = card.color;
+    let outcomes = match self.outcomes(row, column, card) {
+      Some(o) => o,
+      None => return None
+    };
+    let opponents: Vec<(usize, usize)> = self.spaces
+      .iter()
+      .flat_map(|r| r.iter())
+      .filter_map(|s| match *s {
+        Space::Card(ref c) if c.color != color => Some((c.row, c.column)),
+        _ => None
+      })
+      .collect();
+    let before = self.score().get(color) as f64 + 1.0;
+    let mut preview = Preview {
+      flips: vec![0.0; opponents.len() + 1],
+      captured: 0.0,
+      expected_gain: 0.0
+    };
+    for outcome in outcomes {
+      let result = outcome.board;
+      let flips = opponents.iter().filter(|&&(r, c)| result.card(r, c).unwrap().color == color).count();
+      preview.flips[flips] += outcome.chance;
+      if result.card(row, column).unwrap().color != color {
+        preview.captured += outcome.chance;
+      }
+      preview.expected_gain += outcome.chance * (result.score().get(color) as f64 - before);
+    }
+    Some(preview)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use seeded_rng;
+  use fixtures::crowded_match;
+
+  #[test]
+  fn leaf_chances_add_up_to_one() {
+    for seed in 0..100 {
+      let mut rng = seeded_rng(seed);
+      let state = crowded_match(seed);
+      for (row, column) in state.empty_spaces() {
+        let card = OwnedCard::new(Card::random(&mut rng), Color::Blue);
+        let tree = state.board.outcome_tree(row, column, card).unwrap();
+        let total: f64 = tree.leaves().iter().map(|&(_, chance)| chance).sum();
+        assert!((total - 1.0).abs() < 1e-9, "leaves add up to {}", total);
+      }
+    }
+  }
+
+  #[test]
+  fn outcomes_match_rolled_battles() {
+    const RUNS: usize = 2000;
+    let mut checked = 0;
+    for seed in 0..40 {
+      let mut rng = seeded_rng(seed);
+      let state = crowded_match(seed);
+      for (row, column) in state.empty_spaces() {
+        let card = OwnedCard::new(Card::random(&mut rng), Color::Blue);
+        let outcomes = state.board.outcomes(row, column, card.clone()).unwrap();
+        if outcomes.len() < 2 {
+          continue;
+        }
+        checked += 1;
+        let mut counts = vec![0; outcomes.len()];
+        for _ in 0..RUNS {
+          let mut rolled = state.board.clone();
+          rolled.add_card(row, column, card.clone());
+          rolled.run_battles_pos_with_rng(row, column, &mut rng);
+          let i = outcomes.iter().position(|o| o.board == rolled).expect("Rolled board is an outcome");
+          counts[i] += 1;
+        }
+        for (outcome, &count) in outcomes.iter().zip(&counts) {
+          let seen = count as f64 / RUNS as f64;
+          assert!((seen - outcome.chance).abs() < 0.05, "expected {}, saw {}", outcome.chance, seen);
+        }
+      }
+    }
+    assert!(checked > 20);
+  }
+
+  #[test]
+  fn odds_add_up_to_one() {
+    let mut rng = seeded_rng(1);
+    for _ in 0..200 {
+      let odds = TetraMaster::odds(&Card::random(&mut rng), &Card::random(&mut rng));
+      assert!((odds.attacker + odds.defender + odds.draw - 1.0).abs() < 1e-9);
+    }
+  }
+}
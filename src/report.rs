@@ -0,0 +1,159 @@
+use {ArrowRelation, Board, BattleResult, OwnedCard, TetraMaster};
+use state::{GameState, Move};
+
+use rand::Rng;
+
+/// What happened in a single battle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BattleReport {
+  pub attacker: (usize, usize),
+  pub defender: (usize, usize),
+  /// The attacker's final score.
+  pub attack: u8,
+  /// The defender's final score.
+  pub defense: u8,
+  pub result: BattleResult,
+  /// The cards that changed owner because of this battle, starting with the loser.
+  pub flipped: Vec<(usize, usize)>
+}
+
+/// What happened after a card was placed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementReport {
+  pub row: usize,
+  pub column: usize,
+  /// Every battle, in the order it was fought. Draws are included.
+  pub battles: Vec<BattleReport>,
+  /// The cards taken without a battle.
+  pub taken: Vec<(usize, usize)>
+}
+
+impl PlacementReport {
+  /// Every card that changed owner, in the order they changed.
+  pub fn flipped(&self) -> Vec<(usize, usize)> {
+    self.battles
+      .iter()
+      .flat_map(|b| b.flipped.iter().cloned())
+      .chain(self.taken.iter().cloned())
+      .collect()
+  }
+}
+
+impl Board {
+  /// Finds the cards the card at the given location would battle, in the order they are battled.
+  pub fn battle_targets(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+    match self.relations(row, col) {
+      Some((_, relations)) => relations.into_iter()
+        .filter(|&(ref rel, _)| *rel == ArrowRelation::Battle)
+        .map(|(_, pos)| pos)
+        .collect(),
+      None => Vec::new()
+    }
+  }
+
+  /// Runs the battles for the card at the given location and reports what happened.
+  ///
+  /// If `first` is one of the cards to battle, it is battled before the others, which keep their
+  /// usual order. Otherwise this is the same as `run_battles_pos_with_rng`.
+  pub fn run_battles_reported<R: Rng>(&mut self, row: usize, col: usize, first: Option<(usize, usize)>, rng: &mut R) -> PlacementReport {
+    let mut report = PlacementReport {
+      row: row,
+      column: col,
+      battles: Vec::new(),
+      taken: Vec::new()
+    };
+    self.run_battles_into(row, col, first, rng, &mut report);
+    report
+  }
+
+  fn run_battles_into<R: Rng>(&mut self, row: usize, col: usize, first: Option<(usize, usize)>, rng: &mut R, report: &mut PlacementReport) {
+    let (color, relations) = match self.relations(row, col) {
+      Some(r) => r,
+      None => return
+    };
+    let mut battles: Vec<(usize, usize)> = relations.iter()
+      .filter(|&&(ref rel, _)| *rel == ArrowRelation::Battle)
+      .map(|&(_, pos)| pos)
+      .collect();
+    if let Some(i) = first.and_then(|f| battles.iter().position(|&pos| pos == f)) {
+      let pos = battles.remove(i);
+      battles.insert(0, pos);
+    }
+    let mut lost_any = false;
+    for &(r, c) in &battles {
+      let roll = TetraMaster::roll_with_rng(self.card(row, col).unwrap(), self.card(r, c).unwrap(), rng);
+      let mut battle = BattleReport {
+        attacker: (row, col),
+        defender: (r, c),
+        attack: roll.attack,
+        defense: roll.defense,
+        result: roll.result(),
+        flipped: Vec::new()
+      };
+      match battle.result {
+        BattleResult::Attacker => {
+          self.set_color(r, c, color);
+          battle.flipped.push((r, c));
+          battle.flipped.extend(self.do_combo(color, r, c));
+        },
+        BattleResult::Defender => {
+          let defender_color = self.card(r, c).unwrap().color;
+          self.set_color(row, col, defender_color);
+          battle.flipped.push((row, col));
+          battle.flipped.extend(self.do_combo(defender_color, row, col));
+          lost_any = true;
+        },
+        BattleResult::Draw => {
+          report.battles.push(battle);
+          self.run_battles_into(row, col, first, rng, report);
+          return;
+        }
+      }
+      report.battles.push(battle);
+      if lost_any {
+        break;
+      }
+    }
+    if !lost_any {
+      let takes = relations.iter()
+        .filter(|&&(ref rel, _)| *rel == ArrowRelation::Take)
+        .map(|&(_, pos)| pos);
+      for (r, c) in takes {
+        if self.card(r, c).map(|x| x.color != color).unwrap_or(false) {
+          report.taken.push((r, c));
+        }
+        self.set_color(r, c, color);
+      }
+    }
+  }
+}
+
+impl GameState {
+  /// Finds the cards a move would battle, in the order they are battled.
+  ///
+  /// When there is more than one, the player may pick which to battle first.
+  pub fn battle_targets(&self, mv: Move) -> Vec<(usize, usize)> {
+    if !self.is_legal(mv) {
+      return Vec::new();
+    }
+    let mut board = self.board.clone();
+    board.add_card(mv.row, mv.column, OwnedCard::new(self.hand(self.to_move)[mv.card], self.to_move));
+    board.battle_targets(mv.row, mv.column)
+  }
+
+  /// Plays a move like `apply_with_rng`, battling `first` first if it can, and reports what
+  /// happened.
+  ///
+  /// Returns `None` if the move is not legal.
+  pub fn apply_reported<R: Rng>(&mut self, mv: Move, first: Option<(usize, usize)>, rng: &mut R) -> Option<PlacementReport> {
+    if !self.is_legal(mv) {
+      return None;
+    }
+    let color = self.to_move;
+    let card = self.hand_mut(color).remove(mv.card);
+    self.board.add_card(mv.row, mv.column, OwnedCard::new(card, color));
+    let report = self.board.run_battles_reported(mv.row, mv.column, first, rng);
+    self.to_move = color.opponent();
+    Some(report)
+  }
+}
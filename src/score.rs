@@ -0,0 +1,102 @@
+use {Board, Color, PlacedCard, Space};
+use state::GameState;
+
+use std::ops::Add;
+
+/// How a match went for one player.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MatchResult {
+  Win,
+  Loss,
+  Draw,
+  /// A win where the player owns every card.
+  Perfect
+}
+
+/// The number of cards owned by each player.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Score {
+  pub blue: u8,
+  pub red: u8
+}
+
+impl Score {
+  pub fn get(&self, color: Color) -> u8 {
+    match color {
+      Color::Blue => self.blue,
+      Color::Red => self.red
+    }
+  }
+
+  pub fn add_cards(&mut self, color: Color, cards: u8) {
+    match color {
+      Color::Blue => self.blue += cards,
+      Color::Red => self.red += cards
+    }
+  }
+
+  /// Counts cards still in each player's hand as owned by that player, as FF9 does.
+  pub fn with_hands(mut self, blue: usize, red: usize) -> Score {
+    self.add_cards(Color::Blue, blue as u8);
+    self.add_cards(Color::Red, red as u8);
+    self
+  }
+
+  pub fn winner(&self) -> Option<Color> {
+    if self.blue > self.red {
+      Some(Color::Blue)
+    } else if self.red > self.blue {
+      Some(Color::Red)
+    } else {
+      None
+    }
+  }
+
+  pub fn result_for(&self, color: Color) -> MatchResult {
+    let (own, other) = (self.get(color), self.get(color.opponent()));
+    if own > 0 && other == 0 {
+      MatchResult::Perfect
+    } else if own > other {
+      MatchResult::Win
+    } else if own < other {
+      MatchResult::Loss
+    } else {
+      MatchResult::Draw
+    }
+  }
+}
+
+impl<'a> Add<&'a PlacedCard> for Score {
+  type Output = Score;
+
+  fn add(mut self, rhs: &'a PlacedCard) -> Score {
+    self.add_cards(rhs.color, 1);
+    self
+  }
+}
+
+impl Board {
+  /// Counts the cards each player owns on the board.
+  pub fn score(&self) -> Score {
+    self.spaces
+      .iter()
+      .flat_map(|x| x.iter())
+      .filter_map(|s| match *s {
+        Space::Card(ref c) => Some(c),
+        _ => None
+      })
+      .fold(Score::default(), |s, x| s + x)
+  }
+}
+
+impl GameState {
+  /// Counts the cards each player owns, optionally including the cards left in their hands.
+  pub fn score(&self, include_hands: bool) -> Score {
+    let score = self.board.score();
+    if include_hands {
+      score.with_hands(self.blue_hand.len(), self.red_hand.len())
+    } else {
+      score
+    }
+  }
+}
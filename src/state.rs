@@ -0,0 +1,124 @@
+use {Board, Card, Color, Space};
+
+use rand::{thread_rng, Rng};
+
+/// A card from the hand of the player to move, and where to place it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Move {
+  /// The index of the card in the hand.
+  pub card: usize,
+  pub row: usize,
+  pub column: usize
+}
+
+impl Move {
+  pub fn new(card: usize, row: usize, column: usize) -> Self {
+    Move {
+      card: card,
+      row: row,
+      column: column
+    }
+  }
+}
+
+/// Everything about a match in progress: the board, both hands and whose turn it is.
+///
+/// A state is a plain value. Playing a move either changes a state through `&mut` or produces a new
+/// state, so states can be cloned and shared between threads while searching for a move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState {
+  pub board: Board,
+  pub blue_hand: Vec<Card>,
+  pub red_hand: Vec<Card>,
+  pub to_move: Color
+}
+
+impl GameState {
+  pub fn new(board: Board, blue_hand: Vec<Card>, red_hand: Vec<Card>, to_move: Color) -> Self {
+    GameState {
+      board: board,
+      blue_hand: blue_hand,
+      red_hand: red_hand,
+      to_move: to_move
+    }
+  }
+
+  pub fn hand(&self, color: Color) -> &[Card] {
+    match color {
+      Color::Blue => &self.blue_hand,
+      Color::Red => &self.red_hand
+    }
+  }
+
+  pub fn hand_mut(&mut self, color: Color) -> &mut Vec<Card> {
+    match color {
+      Color::Blue => &mut self.blue_hand,
+      Color::Red => &mut self.red_hand
+    }
+  }
+
+  /// Gets the empty spaces of the board, starting at 1.
+  pub fn empty_spaces(&self) -> Vec<(usize, usize)> {
+    let mut empty = Vec::new();
+    for (r, row) in self.board.spaces.iter().enumerate() {
+      for (c, space) in row.iter().enumerate() {
+        if let Space::Empty = *space {
+          empty.push((r + 1, c + 1));
+        }
+      }
+    }
+    empty
+  }
+
+  pub fn legal_moves(&self) -> Vec<Move> {
+    let empty = self.empty_spaces();
+    let mut moves = Vec::with_capacity(empty.len() * self.hand(self.to_move).len());
+    for card in 0..self.hand(self.to_move).len() {
+      for &(row, column) in &empty {
+        moves.push(Move::new(card, row, column));
+      }
+    }
+    moves
+  }
+
+  pub fn is_legal(&self, mv: Move) -> bool {
+    mv.card < self.hand(self.to_move).len()
+      && mv.row >= 1 && mv.row <= self.board.height()
+      && mv.column >= 1 && mv.column <= self.board.width()
+      && self.board.space(mv.row, mv.column).is_empty()
+  }
+
+  /// Whether neither player can move any more.
+  pub fn is_over(&self) -> bool {
+    (self.blue_hand.is_empty() && self.red_hand.is_empty()) || self.empty_spaces().is_empty()
+  }
+
+  /// Plays a move for the player to move and passes the turn, returning false if the move is not
+  /// legal.
+  pub fn apply(&mut self, mv: Move) -> bool {
+    self.apply_with_rng(mv, &mut thread_rng())
+  }
+
+  /// Plays a move like `apply`, rolling battles with the given random number generator.
+  pub fn apply_with_rng<R: Rng>(&mut self, mv: Move, rng: &mut R) -> bool {
+    self.apply_reported(mv, None, rng).is_some()
+  }
+
+  /// Plays a move on a copy of this state, leaving this state untouched.
+  pub fn play(&self, mv: Move) -> Option<GameState> {
+    let mut state = self.clone();
+    if state.apply(mv) {
+      Some(state)
+    } else {
+      None
+    }
+  }
+}
+
+/// States are shared between threads when searching, so they must stay `Send` and `Sync`.
+#[allow(dead_code)]
+fn assert_send_sync() {
+  fn check<T: Send + Sync>() {}
+  check::<GameState>();
+  check::<Board>();
+}
@@ -0,0 +1,236 @@
+use {Arrows, Board, Card, Color, Space};
+use state::{GameState, Move};
+
+/// One of the ways a board can be turned or flipped.
+///
+/// Every symmetry is a number of clockwise quarter turns followed by an optional left-right flip.
+/// Square boards have all eight; other boards only have the four that keep their shape.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Symmetry {
+  Identity,
+  Rotate90,
+  Rotate180,
+  Rotate270,
+  /// Flips left and right.
+  MirrorHorizontal,
+  /// Flips top and bottom.
+  MirrorVertical,
+  /// Flips over the diagonal from the top left to the bottom right.
+  Transpose,
+  /// Flips over the diagonal from the top right to the bottom left.
+  AntiTranspose
+}
+
+pub const SYMMETRIES: [Symmetry; 8] = [
+  Symmetry::Identity,
+  Symmetry::Rotate90,
+  Symmetry::Rotate180,
+  Symmetry::Rotate270,
+  Symmetry::MirrorHorizontal,
+  Symmetry::MirrorVertical,
+  Symmetry::Transpose,
+  Symmetry::AntiTranspose
+];
+
+impl Symmetry {
+  /// The number of clockwise quarter turns, and whether to flip left and right afterwards.
+  fn parts(&self) -> (usize, bool) {
+    match *self {
+      Symmetry::Identity => (0, false),
+      Symmetry::Rotate90 => (1, false),
+      Symmetry::Rotate180 => (2, false),
+      Symmetry::Rotate270 => (3, false),
+      Symmetry::MirrorHorizontal => (0, true),
+      Symmetry::MirrorVertical => (2, true),
+      Symmetry::Transpose => (1, true),
+      Symmetry::AntiTranspose => (3, true)
+    }
+  }
+
+  /// The symmetry that undoes this one.
+  pub fn inverse(&self) -> Symmetry {
+    match *self {
+      Symmetry::Rotate90 => Symmetry::Rotate270,
+      Symmetry::Rotate270 => Symmetry::Rotate90,
+      s => s
+    }
+  }
+
+  /// Whether this symmetry swaps the width and height of a board.
+  pub fn swaps_sides(&self) -> bool {
+    self.parts().0 % 2 == 1
+  }
+
+  /// The symmetries of a board of the given size.
+  pub fn all_for(width: usize, height: usize) -> Vec<Symmetry> {
+    SYMMETRIES.iter().cloned().filter(|s| width == height || !s.swaps_sides()).collect()
+  }
+
+  /// Finds where a location on a board of the given size ends up, with locations starting at 1.
+  pub fn transform(&self, row: usize, column: usize, width: usize, height: usize) -> (usize, usize) {
+    let (turns, mirror) = self.parts();
+    let (mut r, mut c, mut w, mut h) = (row - 1, column - 1, width, height);
+    for _ in 0..turns {
+      let turned = (c, h - 1 - r);
+      r = turned.0;
+      c = turned.1;
+      let sides = (h, w);
+      w = sides.0;
+      h = sides.1;
+    }
+    if mirror {
+      c = w - 1 - c;
+    }
+    (r + 1, c + 1)
+  }
+
+  pub fn transform_arrows(&self, arrows: &Arrows) -> Arrows {
+    let (turns, mirror) = self.parts();
+    let mut arrows = *arrows;
+    for _ in 0..turns {
+      arrows = arrows.rotated();
+    }
+    if mirror {
+      arrows = arrows.mirrored();
+    }
+    arrows
+  }
+}
+
+impl Arrows {
+  /// Turns the arrows a quarter turn clockwise, so north points east, northeast points southeast
+  /// and so on.
+  pub fn rotated(&self) -> Arrows {
+    Arrows::from_flags(self.flags.rotate_right(2))
+  }
+
+  /// Flips the arrows left and right, so east points west and northeast points northwest.
+  pub fn mirrored(&self) -> Arrows {
+    let mut arrows = *self;
+    arrows.set_east(self.west());
+    arrows.set_west(self.east());
+    arrows.set_northeast(self.northwest());
+    arrows.set_northwest(self.northeast());
+    arrows.set_southeast(self.southwest());
+    arrows.set_southwest(self.southeast());
+    arrows
+  }
+}
+
+/// Encodes a space so positions can be compared.
+fn space_key(space: &Space) -> u32 {
+  match *space {
+    Space::Empty => 0,
+    Space::Block => 1,
+    Space::Card(ref c) => {
+      let color = match c.color {
+        Color::Blue => 0,
+        Color::Red => 1
+      };
+      let original = match c.original_color {
+        Color::Blue => 0,
+        Color::Red => 1
+      };
+      2 + ((c.arrows.flags as u32) << 20
+        | (c.power as u32) << 16
+        | (c.class as u32) << 12
+        | (c.physical_defense as u32) << 8
+        | (c.magical_defense as u32) << 4
+        | original << 1
+        | color)
+    }
+  }
+}
+
+fn board_key(board: &Board) -> Vec<u32> {
+  board.spaces.iter().flat_map(|row| row.iter().map(space_key)).collect()
+}
+
+impl Board {
+  /// Turns or flips the board, along with the arrows of every card on it.
+  pub fn transformed(&self, symmetry: Symmetry) -> Board {
+    let (width, height) = (self.width(), self.height());
+    let mut board = if symmetry.swaps_sides() {
+      Board::new(height, width)
+    } else {
+      Board::new(width, height)
+    };
+    for (r, row) in self.spaces.iter().enumerate() {
+      for (c, space) in row.iter().enumerate() {
+        let (new_row, new_column) = symmetry.transform(r + 1, c + 1, width, height);
+        let mut space = space.clone();
+        if let Space::Card(ref mut card) = space {
+          card.card.card.arrows = symmetry.transform_arrows(&card.arrows);
+          card.row = new_row;
+          card.column = new_column;
+        }
+        *board.space_mut(new_row, new_column) = space;
+      }
+    }
+    board
+  }
+
+  /// Turns the board a quarter turn clockwise.
+  pub fn rotated(&self) -> Board {
+    self.transformed(Symmetry::Rotate90)
+  }
+
+  /// Flips the board left and right.
+  pub fn mirrored(&self) -> Board {
+    self.transformed(Symmetry::MirrorHorizontal)
+  }
+
+  /// Finds the symmetry that turns this board into its canonical form.
+  ///
+  /// Boards that are turns or flips of each other all have the same canonical form.
+  pub fn canonical_symmetry(&self) -> Symmetry {
+    Symmetry::all_for(self.width(), self.height())
+      .into_iter()
+      .min_by_key(|s| board_key(&self.transformed(*s)))
+      .unwrap()
+  }
+
+  /// Gets the canonical form of this board, and the symmetry that produced it.
+  pub fn canonical(&self) -> (Board, Symmetry) {
+    let symmetry = self.canonical_symmetry();
+    (self.transformed(symmetry), symmetry)
+  }
+}
+
+impl GameState {
+  /// Turns or flips the board of this game. The arrows of cards in hand are turned along with it.
+  pub fn transformed(&self, symmetry: Symmetry) -> GameState {
+    let turn = |hand: &[Card]| hand.iter()
+      .map(|c| {
+        let mut c = *c;
+        c.arrows = symmetry.transform_arrows(&c.arrows);
+        c
+      })
+      .collect();
+    GameState::new(self.board.transformed(symmetry), turn(&self.blue_hand), turn(&self.red_hand), self.to_move)
+  }
+
+  /// Gets the canonical form of this game, and the symmetry that produced it.
+  ///
+  /// Moves found in the canonical form can be mapped back with `Move::transformed` and the inverse
+  /// of the symmetry.
+  pub fn canonical(&self) -> (GameState, Symmetry) {
+    Symmetry::all_for(self.board.width(), self.board.height())
+      .into_iter()
+      .map(|s| (self.transformed(s), s))
+      .min_by_key(|&(ref state, _)| {
+        let mut key = board_key(&state.board);
+        key.extend(state.blue_hand.iter().chain(state.red_hand.iter()).map(|c| (c.arrows.flags as u32) << 4 | c.power as u32));
+        key
+      })
+      .unwrap()
+  }
+}
+
+impl Move {
+  /// Finds where this move goes on a board of the given size after it is turned or flipped.
+  pub fn transformed(&self, symmetry: Symmetry, width: usize, height: usize) -> Move {
+    let (row, column) = symmetry.transform(self.row, self.column, width, height);
+    Move::new(self.card, row, column)
+  }
+}
@@ -0,0 +1,91 @@
+/// How a stored value relates to the true value of a position.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Bound {
+  Exact,
+  /// The true value is at least the stored value.
+  Lower,
+  /// The true value is at most the stored value.
+  Upper
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry<T> {
+  /// The full hash of the position, to tell apart positions sharing a slot.
+  pub key: u64,
+  /// How deep the position was searched.
+  pub depth: u8,
+  pub bound: Bound,
+  pub value: T
+}
+
+/// A fixed-size cache of search results, indexed by position hash.
+///
+/// When two positions share a slot, the one searched deeper is kept. Any searcher can store what
+/// it likes as the value.
+#[derive(Debug, Clone)]
+pub struct TranspositionTable<T> {
+  entries: Vec<Option<Entry<T>>>,
+  used: usize
+}
+
+impl<T> TranspositionTable<T> {
+  /// Creates a table with at least the given number of slots, rounded up to a power of two.
+  pub fn new(slots: usize) -> Self {
+    let slots = slots.max(1).next_power_of_two();
+    TranspositionTable {
+      entries: (0..slots).map(|_| None).collect(),
+      used: 0
+    }
+  }
+
+  fn slot(&self, key: u64) -> usize {
+    (key as usize) & (self.entries.len() - 1)
+  }
+
+  pub fn get(&self, key: u64) -> Option<&Entry<T>> {
+    match self.entries[self.slot(key)] {
+      Some(ref e) if e.key == key => Some(e),
+      _ => None
+    }
+  }
+
+  /// Stores a result, unless its slot holds a deeper search of another position.
+  pub fn insert(&mut self, key: u64, depth: u8, bound: Bound, value: T) {
+    let slot = self.slot(key);
+    let replace = match self.entries[slot] {
+      Some(ref e) => e.key == key || depth >= e.depth,
+      None => {
+        self.used += 1;
+        true
+      }
+    };
+    if replace {
+      self.entries[slot] = Some(Entry {
+        key: key,
+        depth: depth,
+        bound: bound,
+        value: value
+      });
+    }
+  }
+
+  pub fn clear(&mut self) {
+    for entry in &mut self.entries {
+      *entry = None;
+    }
+    self.used = 0;
+  }
+
+  /// The number of slots in use.
+  pub fn len(&self) -> usize {
+    self.used
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.used == 0
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.entries.len()
+  }
+}
@@ -0,0 +1,249 @@
+use {Board, Card, Color, Space, seeded_rng};
+use state::GameState;
+
+use rand::Rng;
+
+use std::cmp;
+
+/// Mixes the bits of a key, so that keys combined with XOR stay independent of each other.
+fn mix(mut x: u64) -> u64 {
+  x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  x ^ (x >> 31)
+}
+
+fn color_index(color: Color) -> usize {
+  match color {
+    Color::Blue => 0,
+    Color::Red => 1
+  }
+}
+
+/// Random keys for hashing positions of a board of a given size.
+///
+/// Keys are generated from a seed, so the same seed always gives the same hashes.
+#[derive(Debug, Clone)]
+pub struct ZobristKeys {
+  width: usize,
+  blocks: Vec<u64>,
+  /// Keys for a card in each space, by owner.
+  spaces: Vec<[u64; 2]>,
+  /// Keys for a card in each hand.
+  hands: [u64; 2],
+  power: [u64; 16],
+  class: [u64; 4],
+  physical_defense: [u64; 16],
+  magical_defense: [u64; 16],
+  arrows: Vec<u64>,
+  red_to_move: u64
+}
+
+impl ZobristKeys {
+  pub fn new(seed: u64, width: usize, height: usize) -> Self {
+    let mut rng = seeded_rng(seed);
+    let cells = width * height;
+    let mut keys = ZobristKeys {
+      width: width,
+      blocks: (0..cells).map(|_| rng.gen()).collect(),
+      spaces: (0..cells).map(|_| [rng.gen(), rng.gen()]).collect(),
+      hands: [rng.gen(), rng.gen()],
+      power: [0; 16],
+      class: [0; 4],
+      physical_defense: [0; 16],
+      magical_defense: [0; 16],
+      arrows: (0..256).map(|_| rng.gen()).collect(),
+      red_to_move: rng.gen()
+    };
+    for i in 0..16 {
+      keys.power[i] = rng.gen();
+      keys.physical_defense[i] = rng.gen();
+      keys.magical_defense[i] = rng.gen();
+    }
+    for i in 0..4 {
+      keys.class[i] = rng.gen();
+    }
+    keys
+  }
+
+  fn cell(&self, row: usize, column: usize) -> usize {
+    (row - 1) * self.width + (column - 1)
+  }
+
+  /// The key identifying a card, wherever it is.
+  pub fn card(&self, card: &Card) -> u64 {
+    self.power[card.power as usize & 0x0F]
+      ^ self.class[card.class as usize]
+      ^ self.physical_defense[card.physical_defense as usize & 0x0F]
+      ^ self.magical_defense[card.magical_defense as usize & 0x0F]
+      ^ self.arrows[card.arrows.flags as usize]
+  }
+
+  pub fn block(&self, row: usize, column: usize) -> u64 {
+    self.blocks[self.cell(row, column)]
+  }
+
+  /// The key for a card on the board, owned by the given color.
+  pub fn placed(&self, row: usize, column: usize, card: &Card, color: Color) -> u64 {
+    mix(self.spaces[self.cell(row, column)][color_index(color)] ^ self.card(card))
+  }
+
+  /// The key for a card in the given color's hand.
+  pub fn in_hand(&self, card: &Card, color: Color) -> u64 {
+    mix(self.hands[color_index(color)] ^ self.card(card))
+  }
+
+  pub fn red_to_move(&self) -> u64 {
+    self.red_to_move
+  }
+
+  /// Hashes a board from scratch.
+  pub fn hash_board(&self, board: &Board) -> ZobristHash {
+    let mut hash = ZobristHash::default();
+    for (r, row) in board.spaces.iter().enumerate() {
+      for (c, space) in row.iter().enumerate() {
+        match *space {
+          Space::Block => hash.toggle_block(self, r + 1, c + 1),
+          Space::Card(ref card) => hash.toggle_card(self, r + 1, c + 1, &card.card.card, card.color),
+          Space::Empty => {}
+        }
+      }
+    }
+    hash
+  }
+
+  /// Hashes a game from scratch.
+  pub fn hash(&self, state: &GameState) -> ZobristHash {
+    let mut hash = self.hash_board(&state.board);
+    for color in &[Color::Blue, Color::Red] {
+      for card in state.hand(*color) {
+        hash.add_to_hand(self, card, *color);
+      }
+    }
+    if state.to_move == Color::Red {
+      hash.toggle_side(self);
+    }
+    hash
+  }
+
+  /// Updates the hash of a game after a card was placed at the given location.
+  ///
+  /// A placed card only battles its neighbors, and a combo only flips the neighbors of the card
+  /// that lost, so no card more than two spaces from the placed card can change owner. Only the
+  /// spaces that near are compared, and only the cards that changed owner are rehashed.
+  ///
+  /// `before` and `after` are the game before and after the card was placed.
+  pub fn update(&self, hash: ZobristHash, before: &GameState, row: usize, column: usize, after: &GameState) -> ZobristHash {
+    let mut hash = hash;
+    let placed = match *after.board.space(row, column) {
+      Space::Card(ref c) => c,
+      _ => return self.hash(after)
+    };
+    hash.remove_from_hand(self, &placed.card.card, placed.original_color);
+    let rows = row.saturating_sub(2).max(1)..cmp::min(row + 2, before.board.height()) + 1;
+    for r in rows {
+      let columns = column.saturating_sub(2).max(1)..cmp::min(column + 2, before.board.width()) + 1;
+      for c in columns {
+        if let Space::Card(ref old) = *before.board.space(r, c) {
+          let new_color = match *after.board.space(r, c) {
+            Space::Card(ref new) => new.color,
+            _ => continue
+          };
+          if old.color != new_color {
+            hash.toggle_card(self, r, c, &old.card.card, old.color);
+            hash.toggle_card(self, r, c, &old.card.card, new_color);
+          }
+        }
+      }
+    }
+    hash.toggle_card(self, row, column, &placed.card.card, placed.color);
+    if before.to_move != after.to_move {
+      hash.toggle_side(self);
+    }
+    hash
+  }
+}
+
+/// The hash of a position, kept up to date as the position changes.
+///
+/// Cards in hands are summed instead of combined with XOR, so a hand holding two copies of a card
+/// hashes differently to one holding none.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZobristHash {
+  pub board: u64,
+  pub hands: u64
+}
+
+impl ZobristHash {
+  pub fn value(&self) -> u64 {
+    self.board ^ self.hands
+  }
+
+  pub fn toggle_block(&mut self, keys: &ZobristKeys, row: usize, column: usize) {
+    self.board ^= keys.block(row, column);
+  }
+
+  /// Adds a card to the board, or removes it if it is already there.
+  pub fn toggle_card(&mut self, keys: &ZobristKeys, row: usize, column: usize, card: &Card, color: Color) {
+    self.board ^= keys.placed(row, column, card, color);
+  }
+
+  pub fn add_to_hand(&mut self, keys: &ZobristKeys, card: &Card, color: Color) {
+    self.hands = self.hands.wrapping_add(keys.in_hand(card, color));
+  }
+
+  pub fn remove_from_hand(&mut self, keys: &ZobristKeys, card: &Card, color: Color) {
+    self.hands = self.hands.wrapping_sub(keys.in_hand(card, color));
+  }
+
+  pub fn toggle_side(&mut self, keys: &ZobristKeys) {
+    self.board ^= keys.red_to_move();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixtures::{hand, new_match};
+  use generator::BoardGenerator;
+  use state::Move;
+
+  /// Plays random moves to the end of the match, checking each update against a full hash.
+  fn play_out<R: Rng>(keys: &ZobristKeys, mut state: GameState, rng: &mut R) {
+    let mut hash = keys.hash(&state);
+    while !state.is_over() {
+      let moves = state.legal_moves();
+      let mv: Move = moves[rng.gen_range(0, moves.len())];
+      let before = state.clone();
+      state.apply_with_rng(mv, rng);
+      hash = keys.update(hash, &before, mv.row, mv.column, &state);
+      assert_eq!(hash, keys.hash(&state));
+    }
+  }
+
+  #[test]
+  fn same_seed_same_hash() {
+    let state = new_match(3);
+    assert_eq!(ZobristKeys::new(7, 4, 4).hash(&state), ZobristKeys::new(7, 4, 4).hash(&state));
+    assert!(ZobristKeys::new(7, 4, 4).hash(&state) != ZobristKeys::new(8, 4, 4).hash(&state));
+  }
+
+  #[test]
+  fn updates_match_full_hashes() {
+    let keys = ZobristKeys::new(7, 4, 4);
+    for seed in 0..50 {
+      play_out(&keys, new_match(seed), &mut seeded_rng(seed));
+    }
+  }
+
+  #[test]
+  fn updates_match_full_hashes_on_big_boards() {
+    let keys = ZobristKeys::new(7, 8, 6);
+    for seed in 0..10 {
+      let mut rng = seeded_rng(seed);
+      let board = BoardGenerator::new().size(8, 6).generate_with_rng(&mut rng);
+      let size = Board::hand_size(8, 6);
+      let (blue, red) = (hand(&mut rng, size), hand(&mut rng, size));
+      play_out(&keys, GameState::new(board, blue, red, Color::Blue), &mut rng);
+    }
+  }
+}
@@ -0,0 +1,137 @@
+//! Plays a whole match through `tm-server`, with two scripted players connected over TCP.
+
+extern crate tetra_master;
+
+use tetra_master::{seeded_rng, Card, Color};
+use tetra_master::net::{Message, Rules, PROTOCOL_VERSION};
+use tetra_master::state::GameState;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// A player talking to the server a line at a time.
+struct Client {
+  reader: BufReader<TcpStream>,
+  writer: TcpStream
+}
+
+impl Client {
+  fn connect(port: u16) -> Client {
+    let stream = TcpStream::connect(("127.0.0.1", port)).expect("Server is listening");
+    Client {
+      reader: BufReader::new(stream.try_clone().unwrap()),
+      writer: stream
+    }
+  }
+
+  fn send(&mut self, message: Message) {
+    writeln!(self.writer, "{}", message.to_string()).unwrap();
+  }
+
+  fn recv(&mut self) -> Message {
+    let mut line = String::new();
+    self.reader.read_line(&mut line).unwrap();
+    match Message::parse(&line) {
+      Some(Message::Illegal(reason)) => panic!("The server refused a message: {}", reason),
+      Some(message) => message,
+      None => panic!("{:?} is not a message", line)
+    }
+  }
+
+  /// Reads what happened after a move until the state of the match, battling the first card
+  /// offered when asked.
+  fn until_state(&mut self) -> GameState {
+    loop {
+      match self.recv() {
+        Message::Choose(targets) => self.send(Message::First(targets[0])),
+        Message::State(state) => return state,
+        _ => {}
+      }
+    }
+  }
+}
+
+/// Kills the server even if the test fails.
+struct Server {
+  child: Child,
+  /// What the server prints, kept open so that printing doesn't fail.
+  _output: BufReader<ChildStdout>
+}
+
+impl Drop for Server {
+  fn drop(&mut self) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn start_server(seed: u64) -> (Server, u16) {
+  let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+  let mut child = Command::new(env!("CARGO_BIN_EXE_tm-server"))
+    .arg(port.to_string())
+    .arg(seed.to_string())
+    .stdout(Stdio::piped())
+    .spawn()
+    .expect("tm-server starts");
+  let mut output = BufReader::new(child.stdout.take().unwrap());
+  let mut listening = String::new();
+  output.read_line(&mut listening).unwrap();
+  assert!(listening.starts_with("Listening"), "{}", listening);
+  let server = Server {
+    child: child,
+    _output: output
+  };
+  (server, port)
+}
+
+#[test]
+fn two_players_finish_a_match() {
+  let (_server, port) = start_server(42);
+  let mut blue = Client::connect(port);
+  let mut red = Client::connect(port);
+
+  blue.send(Message::Create(PROTOCOL_VERSION, Rules::default()));
+  let table = match blue.recv() {
+    Message::Opened(id) => id,
+    message => panic!("{:?} isn't opened", message)
+  };
+  red.send(Message::List);
+  assert_eq!(red.recv(), Message::Tables(vec![(table, Rules::default())]));
+  red.send(Message::Sit(table));
+  assert_eq!(blue.recv(), Message::Hello(PROTOCOL_VERSION, Rules::default()));
+  assert_eq!(red.recv(), Message::Hello(PROTOCOL_VERSION, Rules::default()));
+
+  let mut rng = seeded_rng(42);
+  for client in &mut [&mut blue, &mut red] {
+    let hand = (0..5).map(|_| Card::random(&mut rng)).collect();
+    client.send(Message::Join(PROTOCOL_VERSION, hand));
+  }
+  let mut state = None;
+  for &mut (ref mut client, color) in &mut [(&mut blue, Color::Blue), (&mut red, Color::Red)] {
+    match client.recv() {
+      Message::Start(c, s) => {
+        assert_eq!(c, color);
+        state = Some(s);
+      },
+      message => panic!("{:?} isn't start", message)
+    }
+    match client.recv() {
+      Message::Token(_) => {},
+      message => panic!("{:?} isn't a token", message)
+    }
+  }
+
+  let mut state = state.unwrap();
+  let mut placements = 0;
+  while !state.is_over() {
+    let mv = state.legal_moves()[0];
+    let (mover, other) = if state.to_move == Color::Blue { (&mut blue, &mut red) } else { (&mut red, &mut blue) };
+    mover.send(Message::Move(mv, None));
+    state = mover.until_state();
+    assert_eq!(other.until_state(), state);
+    placements += 1;
+  }
+  assert_eq!(placements, 10);
+  assert!(state.hand(Color::Blue).is_empty() && state.hand(Color::Red).is_empty());
+}